@@ -1,484 +1,999 @@
-#![feature(proc_macro_diagnostic)]
-
-use proc_macro2::Span;
-
-#[macro_use]
-mod shared_array;
-use shared_array::*;
-
-#[macro_use]
-mod shared_map;
-use shared_map::*;
-
-mod symbol;
-use symbol::*;
-
-mod tree_semantics;
-use syn::spanned::Spanned;
-use tree_semantics::*;
-
-mod processing;
-use processing::*;
-
-// use std::iter::FromIterator;
-use proc_macro::TokenStream;
-// use proc_macro2::Span;
-use quote::{quote, ToTokens};
-// use quote::{quote, quote_spanned};
-use syn::parse::{Parse, ParseStream, Result};
-use syn::punctuated::Punctuated;
-use syn::token::Comma;
-// use syn::spanned::Spanned;
-use syn::{braced, parenthesized, parse_macro_input, Attribute, Expr, FnArg, Generics, Ident, Pat, Path, Stmt, Token, Type, Visibility, WhereClause};
-
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::hash::Hash;
-use std::ops::Deref;
-use std::rc::{Rc, Weak};
-use std::str::FromStr;
-use by_address::ByAddress;
-
-/// Data module name.
-const DATA: &'static str = "__data__";
-
-const DATA_PREFIX: &'static str = "__data_";
-
-/// Field name used for holding an enumeration of subtypes.
-const DATA_VARIANT_FIELD: &'static str = "__variant";
-
-/// Prefix used for enumerations of subtypes.
-const DATA_VARIANT_PREFIX: &'static str = "__variant_";
-
-/// Variant name used for indicating that no subtype is instantiated.
-const DATA_VARIANT_NO_SUBTYPE: &'static str = "__Nothing";
-
-struct SmTypeTree {
-    sem_path: proc_macro2::TokenStream,
-    arena_type_name: proc_macro2::TokenStream,
-    data_types: Vec<Rc<SmType>>,
-}
-
-struct SmType {
-    attributes: Vec<Attribute>,
-    visibility: Visibility,
-    name: Ident,
-    inherits: Option<Ident>,
-    fields: Vec<Rc<SmTypeField>>,
-    constructor: Option<SmTypeConstructor>,
-    methods: Vec<Rc<SmTypeMethod>>,
-}
-
-struct SmTypeField {
-    is_ref: bool,
-    name: Ident,
-    type_annotation: Type,
-    default_value: Expr,
-}
-
-enum SmTypeMethodOrConstructor {
-    Method(SmTypeMethod),
-    Constructor(SmTypeConstructor),
-}
-
-struct SmTypeConstructor {
-    attributes: Vec<Attribute>,
-    visibility: Visibility,
-    generics: Generics,
-    name: Ident,
-    inputs: Punctuated<FnArg, Comma>,
-    super_arguments: Punctuated<Expr, Comma>,
-    statements: Vec<Stmt>,
-}
-
-struct SmTypeMethod {
-    attributes: RefCell<Vec<Attribute>>,
-    visibility: Visibility,
-    is_override: bool,
-    name: Ident,
-    generics: Generics,
-    inputs: Punctuated<FnArg, Comma>,
-    result_type: Option<Type>,
-    statements: proc_macro2::TokenStream,
-}
-
-impl Parse for SmTypeTree {
-    fn parse(input: ParseStream) -> Result<Self> {
-        let mut sem_path: Option<Path> = None;
-        if input.peek(Token![mod]) {
-            input.parse::<Token![mod]>()?;
-            input.parse::<Ident>()?;
-            input.parse::<Token![=]>()?;
-            sem_path = Some(parse_full_qualified_id(input)?);
-            input.parse::<Token![;]>()?;
-        }
-        let arena_type_name = parse_smtype_arena_type_name(input)?.to_token_stream();
-        let mut data_types = vec![];
-        while !input.is_empty() {
-            data_types.push(Rc::new(input.parse::<SmType>()?));
-        }
-        Ok(Self {
-            sem_path: sem_path.map(|p| p.to_token_stream()).unwrap_or(proc_macro2::TokenStream::from_str("::hydroperx_sem").unwrap()),
-            arena_type_name,
-            data_types,
-        })
-    }
-}
-
-fn parse_full_qualified_id(input: ParseStream) -> Result<Path> {
-    Ok(Path::parse_mod_style(input)?)
-}
-
-impl Parse for SmType {
-    fn parse(input: ParseStream) -> Result<Self> {
-        let attributes = Attribute::parse_outer(input)?;
-        let visibility = input.parse::<Visibility>()?;
- 
-        input.parse::<Token![struct]>()?;
- 
-        let name = input.parse::<Ident>()?;
-        let name_str = name.to_string();
-
-        // Inherits
-        let mut inherits: Option<Ident> = None;
-        if input.peek(Token![:]) {
-            input.parse::<Token![:]>()?;
-            inherits = Some(input.parse::<Ident>()?);
-        }
-
-        let mut fields: Vec<Rc<SmTypeField>> = vec![];
-        let mut constructor: Option<SmTypeConstructor> = None;
-        let mut methods: Vec<Rc<SmTypeMethod>> = vec![];
-        let braced_content;
-        let _ = braced!(braced_content in input);
-
-        while !braced_content.is_empty() {
-            if braced_content.peek(Token![let]) {
-                fields.push(Rc::new(parse_smtype_field(&braced_content)?));
-            } else {
-                match parse_smtype_method(&braced_content, &name_str)? {
-                    SmTypeMethodOrConstructor::Constructor(ctor) => {
-                        constructor = Some(ctor);
-                    },
-                    SmTypeMethodOrConstructor::Method(m) => {
-                        methods.push(Rc::new(m));
-                    },
-                }
-            }
-        }
-
-        Ok(Self {
-            attributes,
-            visibility,
-            name,
-            inherits,
-            fields,
-            constructor,
-            methods,
-        })
-    }
-}
-
-fn parse_smtype_field(input: ParseStream) -> Result<SmTypeField> {
-    input.parse::<Token![let]>()?;
-    let is_ref = if input.peek(Token![ref]) {
-        input.parse::<Token![ref]>()?;
-        true
-    } else {
-        false
-    };
-    let name = input.parse::<Ident>()?;
-    input.parse::<Token![:]>()?;
-    let type_annotation = input.parse::<Type>()?;
-    input.parse::<Token![=]>()?;
-    let default_value = input.parse::<Expr>()?;
-    input.parse::<Token![;]>()?;
-
-    Ok(SmTypeField {
-        is_ref,
-        name,
-        type_annotation,
-        default_value,
-    })
-}
-
-fn parse_smtype_method(input: ParseStream, smtype_name: &str) -> Result<SmTypeMethodOrConstructor> {
-    let attributes = Attribute::parse_outer(input)?;
-    let visibility = input.parse::<Visibility>()?;
-    let is_override = if input.peek(Token![override]) {
-        input.parse::<Token![override]>()?;
-        true
-    } else {
-        false
-    };
-    input.parse::<Token![fn]>()?;
-    let mut is_constructor = false;
-    let id = input.parse::<Ident>()?;
-    if !is_override && id.to_string() == smtype_name {
-        // id.span().unwrap().error("Identifier must be equals \"constructor\"").emit();
-        is_constructor = true;
-    }
-    let mut generics = input.parse::<Generics>()?;
-
-    let parens_content;
-    parenthesized!(parens_content in input);
-    let inputs = parens_content.parse_terminated(FnArg::parse, Comma)?;
-
-    let result_type: Option<Type> = if !is_constructor && input.peek(Token![->]) {
-        input.parse::<Token![->]>()?;
-        Some(input.parse::<Type>()?)
-    } else {
-        None
-    };
-
-    generics.where_clause = if input.peek(Token![where]) { Some(input.parse::<WhereClause>()?) } else { None };
-
-    let braced_content;
-    let _ = braced!(braced_content in input);
-
-    if !is_constructor {
-        let statements = braced_content.parse::<proc_macro2::TokenStream>()?;
-        return Ok(SmTypeMethodOrConstructor::Method(SmTypeMethod {
-            attributes: RefCell::new(attributes),
-            visibility,
-            is_override,
-            name: id,
-            generics,
-            inputs,
-            result_type,
-            statements,
-        }));
-    }
-
-    braced_content.parse::<Token![super]>()?;
-
-    let paren_content;
-    let _ = parenthesized!(paren_content in braced_content);
-    let super_arguments = paren_content.parse_terminated(Expr::parse, Comma)?;
-    braced_content.parse::<Token![;]>()?;
-
-    let mut statements = vec![];
-    while !braced_content.is_empty() {
-        statements.push(braced_content.parse::<Stmt>()?);
-    }
-
-    Ok(SmTypeMethodOrConstructor::Constructor(SmTypeConstructor {
-        attributes,
-        visibility,
-        generics,
-        name: id,
-        inputs,
-        super_arguments,
-        statements,
-    }))
-}
-
-fn parse_smtype_arena_type_name(input: ParseStream) -> Result<Path> {
-    input.parse::<Token![type]>()?;
-    let id = input.parse::<Ident>()?;
-    if id.to_string() != "Arena" {
-        id.span().unwrap().error("Identifier must be equals \"Arena\"").emit();
-    }
-    input.parse::<Token![=]>()?;
-    let path = Path::parse_mod_style(input)?;
-    input.parse::<Token![;]>()?;
-    Ok(path)
-}
-
-#[proc_macro]
-pub fn sem(input: TokenStream) -> TokenStream {
-    let SmTypeTree {
-        sem_path, arena_type_name, data_types
-    } = parse_macro_input!(input as SmTypeTree);
-
-    let mut host = SemHost::new();
-
-    // # Validations
-
-    // 1. Ensure there is at least one data type.
-
-    if data_types.is_empty() {
-        panic!("There must be at least one data type.");
-    }
-
-    // 2. Ensure the first type inherits no other one.
-
-    if data_types[0].inherits.is_some() {
-        data_types[0].name.span().unwrap().error("First data type must inherit no base.").emit();
-        return TokenStream::new();
-    }
-    let base_smtype_data_name = Ident::new(&(DATA_PREFIX.to_string() + &data_types[0].name.to_string()), Span::call_site());
-
-    // 3. Ensure all other types inherit another one.
-
-    for m in data_types[1..].iter() {
-        if m.inherits.is_none() {
-            m.name.span().unwrap().error("Data type must inherit a base.").emit();
-            return TokenStream::new();
-        }
-    }
-
-    // # Processing steps
-
-    let data_id = Ident::new(DATA, Span::call_site());
-
-    // 1. Output the arena type.
-    host.output.extend::<TokenStream>(quote! {
-        pub type #arena_type_name = #sem_path::Arena<#data_id::#base_smtype_data_name>;
-    }.try_into().unwrap());
-
-    // 2. Traverse each type in a first pass.
-    for smtype_node in data_types.iter() {
-        if !ProcessingStep2().exec(&mut host, smtype_node) {
-            return TokenStream::new();
-        }
-    }
-
-    // 3. Traverse each type in a second pass.
-    for smtype_node in data_types.iter() {
-        let Some(smtype) = host.semantics.get(smtype_node) else {
-            continue;
-        };
-
-        let asc_smtype_list = smtype.asc_smtype_list();
-        let mut field_output = proc_macro2::TokenStream::new();
-        let smtype_name = smtype.name();
-
-        // 3.1. Write out the base data accessor
-        //
-        // A `Weak<#DATA::FirstM>` value.
-        //
-        // For example, for the basemost data type, this
-        // is always "self.0"; for a direct subtype of the basemost
-        // data type, this is always "self.0.0".
-
-        let mut base_accessor = "self.0".to_owned();
-        let mut m1 = smtype.clone();
-        while let Some(m2) = m1.inherits() {
-            base_accessor.push_str(".0");
-            m1 = m2;
-        }
-
-        // 3.2. Traverse each field.
-        for field in smtype_node.fields.iter() {
-            if !ProcessingStep3_2().exec(&mut host, &smtype, field, &base_accessor, &asc_smtype_list, &mut field_output) {
-                return TokenStream::new();
-            }
-        }
-
-        // 3.3. Contribute a #DATA_VARIANT_FIELD field to #DATA::M
-        // holding the enumeration of subtypes.
-        let subtype_enum = Ident::new(&(DATA_VARIANT_PREFIX.to_owned() + &smtype_name), Span::call_site());
-        let data_variant_field_id = Ident::new(DATA_VARIANT_FIELD, Span::call_site());
-        field_output.extend(quote! {
-            pub #data_variant_field_id: #subtype_enum,
-        });
-
-        // 3.4. Contribute an enumeration of subtypes at the `#DATA` module.
-        let mut variants: Vec<proc_macro2::TokenStream> = vec![];
-        for subtype in smtype.subtypes().iter() {
-            let sn = DATA_PREFIX.to_owned() + &subtype.name();
-            variants.push(proc_macro2::TokenStream::from_str(&format!("{sn}(::std::rc::Rc<{sn}>)")).unwrap());
-        }
-        let data_variant_no_subtype = Ident::new(DATA_VARIANT_NO_SUBTYPE, Span::call_site());
-        variants.push(data_variant_no_subtype.to_token_stream());
-        host.data_output.extend(quote! {
-            pub enum #subtype_enum {
-                #(#variants),*
-            }
-        });
-
-        let smtype_data_id = Ident::new(&format!("{DATA_PREFIX}{}", smtype_name), Span::call_site());
-
-        // 3.5. Define the data structure #DATA::M at the #DATA module output,
-        // containing all field output.
-        host.data_output.extend(quote! {
-            pub struct #smtype_data_id {
-                #field_output
-            }
-        });
-
-        // 3.6. Define the structure M
-        ProcessingStep3_6().exec(&mut host, &smtype_node, &smtype, &base_accessor, &sem_path);
-
-        // 3.7. Define the constructor
-        ProcessingStep3_7().exec(&mut host, smtype_node.constructor.as_ref(), &smtype, &asc_smtype_list, &arena_type_name.to_string());
-
-        // 3.8. Traverse each method
-        for method in smtype_node.methods.iter() {
-            if !ProcessingStep3_8().exec(&mut host, method, &smtype) {
-                return TokenStream::new();
-            }
-        }
-    }
-
-    // 4. Traverse each type in a third pass.
-    for smtype_node in data_types.iter() {
-        let Some(smtype) = host.semantics.get(smtype_node) else {
-            continue;
-        };
-
-        let smtype_name = smtype.name();
-        let smtype_name_id = Ident::new(&smtype_name, Span::call_site());
-
-        // 4.1. Traverse each method
-        for method in smtype_node.methods.iter() {
-            ProcessingStep4_1().exec(&mut host, method, &smtype);
-        }
-
-        // * Contribute a `to::<T: TryFrom<M>>()` method.
-        // * Contribute an `is::<T>()` method.
-        smtype.method_output().borrow_mut().extend(quote! {
-            pub fn to<T: TryFrom<#smtype_name_id, Error = #sem_path::SemError>>(&self) -> Result<T, #sem_path::SemError> {
-                T::try_from(self.clone())
-            }
-            pub fn is<T: TryFrom<#smtype_name_id, Error = #sem_path::SemError>>(&self) -> bool {
-                T::try_from(self.clone()).is_ok()
-            }
-        });
-
-        let method_output = smtype.method_output().borrow().clone();
-
-        // Output the code of all methods to an `impl` block for the data type.
-        host.output.extend::<TokenStream>(quote! {
-            impl #smtype_name_id {
-                #method_output
-            }
-        }.try_into().unwrap());
-    }
-
-    let data_output = host.data_output;
-
-    // 5. Output the `mod #DATA { use super::*; ... }` module with its respective contents
-    host.output.extend::<TokenStream>(quote! {
-        #[allow(non_camel_case_types, non_snake_case)]
-        mod #data_id {
-            use super::*;
-
-            #data_output
-        }
-    }.try_into().unwrap());
-
-    // 5. Return output.
-    host.output
-}
-
-fn convert_function_input_to_arguments(input: &Punctuated<FnArg, Comma>) -> Punctuated<proc_macro2::TokenStream, Comma> {
-    let mut out = Punctuated::<proc_macro2::TokenStream, Comma>::new();
-    for arg in input.iter() {
-        if let FnArg::Receiver(_) = arg {
-            arg.span().unwrap().error("Unexpected receiver.").emit();
-            continue;
-        } else {
-            let FnArg::Typed(pt) = arg else {
-                panic!();
-            };
-            let Pat::Ident(id) = pt.pat.as_ref() else {
-                pt.pat.span().unwrap().error("Pattern must be an identifier.").emit();
-                continue;
-            };
-            out.push(id.to_token_stream());
-        }
-    }
-    out
-}
+use proc_macro2::Span;
+
+#[macro_use]
+mod shared_array;
+use shared_array::*;
+
+#[macro_use]
+mod shared_map;
+use shared_map::*;
+
+mod symbol;
+use symbol::*;
+
+mod tree_semantics;
+use syn::spanned::Spanned;
+use tree_semantics::*;
+
+mod processing;
+use processing::*;
+
+// use std::iter::FromIterator;
+use proc_macro::TokenStream;
+// use proc_macro2::Span;
+use quote::{quote, quote_spanned, ToTokens};
+// use quote::{quote, quote_spanned};
+use syn::parse::{Parse, ParseStream, Result};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+// use syn::spanned::Spanned;
+use syn::parse::Parser;
+use syn::{braced, parenthesized, parse_macro_input, Attribute, Expr, FnArg, GenericArgument, GenericParam, Generics, Ident, Pat, PatIdent, Path, PathArguments, Stmt, Token, Type, Visibility, WhereClause};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+use by_address::ByAddress;
+
+/// Prefix of the data module name, which is followed by
+/// the name of the first basemost data type.
+const DATA: &'static str = "__data__";
+
+const DATA_PREFIX: &'static str = "__data_";
+
+/// Field name used for holding the data of the inherited base
+/// within the data of a data type, in the same segment.
+const DATA_BASE_FIELD: &str = "__base";
+
+/// Prefix of the trait implemented by the data of each data type of a segment,
+/// which is followed by the name of the first data type of the segment.
+const DATA_SEGMENT_PREFIX: &str = "__segment_";
+
+/// Prefix of the methods of a segment trait returning the data of
+/// a data type of the segment, which is followed by the data type name.
+const DATA_AS_PREFIX: &str = "__as_";
+
+/// Field name used for holding the generic type parameters
+/// of a data type that its fields may not use.
+const DATA_PHANTOM_FIELD: &str = "__phantom";
+
+/// Field name used for holding the type tag of an instance at the data
+/// of the first data type of a segment, identifying the most specific
+/// data type of the instance within the segment.
+const DATA_TAG_FIELD: &str = "__tag";
+
+/// Field name used for holding, at the data of the first data type of
+/// a segment, the data of a subtype defined by another `sem!` invocation
+/// or of a generic subtype, if any.
+const DATA_EXTENSION_FIELD: &str = "__ext";
+
+/// Prefix of the trait dispatching methods to subtypes defined by
+/// other `sem!` invocations.
+const VTABLE_PREFIX: &str = "__vt_";
+
+/// Prefix of the zero-sized type implementing the vtable trait of the inherited
+/// base of a segment, which is followed by the name of the first data type of the segment.
+const EXTENSION_VTABLE_PREFIX: &str = "__ext_";
+
+/// Name of the method returning the data of a subtype defined by another
+/// `sem!` invocation, downcasted to a concrete type.
+const EXTENSION_DATA_METHOD: &str = "__sem_ext_data";
+
+/// Name of the method returning the name of the most specific
+/// data type of an instance.
+const TYPE_NAME_METHOD: &str = "__sem_type_name";
+
+/// Name of the vtable method tracing the data of a subtype defined
+/// by another `sem!` invocation or of a generic subtype.
+const TRACE_METHOD: &str = "__sem_trace";
+
+/// Name of the method allocating a data type whose subtype is defined
+/// by another `sem!` invocation.
+const EXTENSION_NEW_METHOD: &str = "__sem_new_ext";
+
+/// Prefix of the name binding a forwarded parameter that is not bound to
+/// a plain identifier, which is followed by the position of the parameter.
+const ARG_PREFIX: &str = "__arg";
+
+struct SmTypeTree {
+    sem_path: proc_macro2::TokenStream,
+    arena_type_name: Option<proc_macro2::TokenStream>,
+    handle_kind: HandleKind,
+    data_types: Vec<Rc<SmType>>,
+}
+
+/// The handle wrapped by the basemost data types, given by
+/// the `type Handle = ...;` directive.
+#[derive(Clone, Copy, PartialEq)]
+enum HandleKind {
+    /// A `Weak` reference to the data, held by an `Arena`.
+    Weak,
+    /// A `Copy` index into an `IndexArena`, checked by generation.
+    Index,
+    /// A `std::sync::Weak` reference to the data, held by a `sync::Arena`,
+    /// with lock-based field storage.
+    Sync,
+}
+
+struct SmType {
+    attributes: Vec<Attribute>,
+    visibility: Visibility,
+    is_abstract: bool,
+    is_sealed: bool,
+    name: Ident,
+    generics: Generics,
+    inherits: Option<Path>,
+    fields: Vec<Rc<SmTypeField>>,
+    constructor: Option<SmTypeConstructor>,
+    methods: Vec<Rc<SmTypeMethod>>,
+}
+
+struct SmTypeField {
+    attributes: Vec<Attribute>,
+    /// Visibility of the getter.
+    visibility: Visibility,
+    /// Visibility of the setter.
+    setter_visibility: Visibility,
+    is_ref: bool,
+    /// Whether the field is marked with `#[sem(untraced)]`.
+    is_untraced: bool,
+    name: Ident,
+    type_annotation: Type,
+    default_value: Expr,
+}
+
+enum SmTypeMethodOrConstructor {
+    Method(Box<SmTypeMethod>),
+    Constructor(Box<SmTypeConstructor>),
+}
+
+struct SmTypeConstructor {
+    attributes: Vec<Attribute>,
+    visibility: Visibility,
+    generics: Generics,
+    name: Ident,
+    inputs: Punctuated<FnArg, Comma>,
+    super_arguments: Punctuated<Expr, Comma>,
+    statements: Vec<Stmt>,
+}
+
+struct SmTypeMethod {
+    attributes: RefCell<Vec<Attribute>>,
+    visibility: Visibility,
+    is_override: bool,
+    is_abstract: bool,
+    name: Ident,
+    generics: Generics,
+    inputs: Punctuated<FnArg, Comma>,
+    result_type: Option<Type>,
+    statements: proc_macro2::TokenStream,
+}
+
+impl Parse for SmTypeTree {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut sem_path: Option<Path> = None;
+        if input.peek(Token![mod]) {
+            input.parse::<Token![mod]>()?;
+            input.parse::<Ident>()?;
+            input.parse::<Token![=]>()?;
+            sem_path = Some(parse_full_qualified_id(input)?);
+            input.parse::<Token![;]>()?;
+        }
+        let mut arena_type_name = None;
+        let mut handle_kind = HandleKind::Weak;
+        while input.peek(Token![type]) {
+            if peek_smtype_handle_directive(input) {
+                handle_kind = parse_smtype_handle_kind(input)?;
+            } else {
+                arena_type_name = Some(parse_smtype_arena_type_name(input)?.to_token_stream());
+            }
+        }
+        let mut data_types = vec![];
+        while !input.is_empty() {
+            data_types.push(Rc::new(input.parse::<SmType>()?));
+        }
+        Ok(Self {
+            sem_path: sem_path.map(|p| p.to_token_stream()).unwrap_or(quote! { ::hydroperx_sem }),
+            arena_type_name,
+            handle_kind,
+            data_types,
+        })
+    }
+}
+
+fn parse_full_qualified_id(input: ParseStream) -> Result<Path> {
+    Ok(Path::parse_mod_style(input)?)
+}
+
+impl Parse for SmType {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attributes = Attribute::parse_outer(input)?;
+        let visibility = input.parse::<Visibility>()?;
+        // The `abstract` and `sealed` modifiers may appear in any order.
+        let mut is_abstract = false;
+        let mut is_sealed = false;
+        loop {
+            if input.peek(Token![abstract]) {
+                input.parse::<Token![abstract]>()?;
+                is_abstract = true;
+            } else if input.peek(Ident) && input.fork().parse::<Ident>()? == "sealed" {
+                input.parse::<Ident>()?;
+                is_sealed = true;
+            } else {
+                break;
+            }
+        }
+ 
+        input.parse::<Token![struct]>()?;
+ 
+        let name = input.parse::<Ident>()?;
+        let name_str = name.to_string();
+        let mut generics = input.parse::<Generics>()?;
+
+        // Inherits
+        let mut inherits: Option<Path> = None;
+        if input.peek(Token![:]) {
+            input.parse::<Token![:]>()?;
+            inherits = Some(input.parse::<Path>()?);
+        }
+
+        generics.where_clause = if input.peek(Token![where]) { Some(input.parse::<WhereClause>()?) } else { None };
+
+        let mut fields: Vec<Rc<SmTypeField>> = vec![];
+        let mut constructor: Option<SmTypeConstructor> = None;
+        let mut methods: Vec<Rc<SmTypeMethod>> = vec![];
+        let braced_content;
+        let _ = braced!(braced_content in input);
+
+        while !braced_content.is_empty() {
+            if peek_smtype_field(&braced_content) {
+                fields.push(Rc::new(parse_smtype_field(&braced_content)?));
+            } else {
+                match parse_smtype_method(&braced_content, &name_str)? {
+                    SmTypeMethodOrConstructor::Constructor(ctor) => {
+                        constructor = Some(*ctor);
+                    },
+                    SmTypeMethodOrConstructor::Method(m) => {
+                        methods.push(Rc::from(m));
+                    },
+                }
+            }
+        }
+
+        Ok(Self {
+            attributes,
+            visibility,
+            is_abstract,
+            is_sealed,
+            name,
+            generics,
+            inherits,
+            fields,
+            constructor,
+            methods,
+        })
+    }
+}
+
+/// Whether a field follows, possibly preceded by visibilities.
+fn peek_smtype_field(input: ParseStream) -> bool {
+    let fork = input.fork();
+    if fork.call(Attribute::parse_outer).is_err() || fork.parse::<Visibility>().is_err() {
+        return false;
+    }
+    if fork.peek(Token![let]) {
+        return true;
+    }
+    fork.parse::<Visibility>().is_ok() && peek_setter_keyword(&fork)
+}
+
+/// Whether the `set` contextual keyword follows, after a setter visibility.
+fn peek_setter_keyword(input: ParseStream) -> bool {
+    input.fork().parse::<Ident>().map(|id| id == "set").unwrap_or(false) && input.peek2(Token![let])
+}
+
+fn parse_smtype_field(input: ParseStream) -> Result<SmTypeField> {
+    let mut attributes = input.call(Attribute::parse_outer)?;
+    // `#[sem(untraced)]` leaves the field out of tracing by `collect()`.
+    let mut is_untraced = false;
+    for attribute in attributes.iter().filter(|a| a.path().is_ident("sem")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("untraced") {
+                is_untraced = true;
+                Ok(())
+            } else {
+                Err(meta.error("Unsupported `sem` attribute: expected `untraced`."))
+            }
+        })?;
+    }
+    attributes.retain(|a| !a.path().is_ident("sem"));
+    // The visibility applies to both the getter and the setter, unless
+    // a setter visibility follows, as in `pub pub(crate) set let`.
+    let visibility = input.parse::<Visibility>()?;
+    let fork = input.fork();
+    let setter_visibility = if fork.parse::<Visibility>().is_ok() && peek_setter_keyword(&fork) {
+        let setter_visibility = input.parse::<Visibility>()?;
+        input.parse::<Ident>()?;
+        setter_visibility
+    } else {
+        visibility.clone()
+    };
+    input.parse::<Token![let]>()?;
+    let is_ref = if input.peek(Token![ref]) {
+        input.parse::<Token![ref]>()?;
+        true
+    } else {
+        false
+    };
+    let name = input.parse::<Ident>()?;
+    input.parse::<Token![:]>()?;
+    let type_annotation = input.parse::<Type>()?;
+    input.parse::<Token![=]>()?;
+    let default_value = input.parse::<Expr>()?;
+    input.parse::<Token![;]>()?;
+
+    Ok(SmTypeField {
+        attributes,
+        visibility,
+        setter_visibility,
+        is_ref,
+        is_untraced,
+        name,
+        type_annotation,
+        default_value,
+    })
+}
+
+fn parse_smtype_method(input: ParseStream, smtype_name: &str) -> Result<SmTypeMethodOrConstructor> {
+    let attributes = Attribute::parse_outer(input)?;
+    let visibility = input.parse::<Visibility>()?;
+    let abstract_token = if input.peek(Token![abstract]) {
+        Some(input.parse::<Token![abstract]>()?)
+    } else {
+        None
+    };
+    let is_abstract = abstract_token.is_some();
+    let is_override = if input.peek(Token![override]) {
+        let override_token = input.parse::<Token![override]>()?;
+        if is_abstract {
+            return Err(syn::Error::new(override_token.span(), "An abstract method cannot override another method."));
+        }
+        true
+    } else {
+        false
+    };
+    input.parse::<Token![fn]>()?;
+    let mut is_constructor = false;
+    let id = input.parse::<Ident>()?;
+    if !is_override && id.to_string() == smtype_name {
+        if let Some(abstract_token) = abstract_token {
+            return Err(syn::Error::new(abstract_token.span(), "A constructor cannot be abstract."));
+        }
+        // id.span().unwrap().error("Identifier must be equals \"constructor\"").emit();
+        is_constructor = true;
+    }
+    let mut generics = input.parse::<Generics>()?;
+
+    let parens_content;
+    parenthesized!(parens_content in input);
+    let inputs = parens_content.parse_terminated(FnArg::parse, Comma)?;
+
+    let result_type: Option<Type> = if !is_constructor && input.peek(Token![->]) {
+        input.parse::<Token![->]>()?;
+        Some(input.parse::<Type>()?)
+    } else {
+        None
+    };
+
+    generics.where_clause = if input.peek(Token![where]) { Some(input.parse::<WhereClause>()?) } else { None };
+
+    // An abstract method has no body.
+    if is_abstract {
+        input.parse::<Token![;]>()?;
+        return Ok(SmTypeMethodOrConstructor::Method(Box::new(SmTypeMethod {
+            attributes: RefCell::new(attributes),
+            visibility,
+            is_override,
+            is_abstract,
+            name: id,
+            generics,
+            inputs,
+            result_type,
+            statements: proc_macro2::TokenStream::new(),
+        })));
+    }
+
+    let braced_content;
+    let _ = braced!(braced_content in input);
+
+    if !is_constructor {
+        let statements = braced_content.parse::<proc_macro2::TokenStream>()?;
+        return Ok(SmTypeMethodOrConstructor::Method(Box::new(SmTypeMethod {
+            attributes: RefCell::new(attributes),
+            visibility,
+            is_override,
+            is_abstract,
+            name: id,
+            generics,
+            inputs,
+            result_type,
+            statements,
+        })));
+    }
+
+    braced_content.parse::<Token![super]>()?;
+
+    let paren_content;
+    let _ = parenthesized!(paren_content in braced_content);
+    let super_arguments = paren_content.parse_terminated(Expr::parse, Comma)?;
+    braced_content.parse::<Token![;]>()?;
+
+    let mut statements = vec![];
+    while !braced_content.is_empty() {
+        statements.push(braced_content.parse::<Stmt>()?);
+    }
+
+    Ok(SmTypeMethodOrConstructor::Constructor(Box::new(SmTypeConstructor {
+        attributes,
+        visibility,
+        generics,
+        name: id,
+        inputs,
+        super_arguments,
+        statements,
+    })))
+}
+
+fn parse_smtype_arena_type_name(input: ParseStream) -> Result<Path> {
+    input.parse::<Token![type]>()?;
+    let id = input.parse::<Ident>()?;
+    if id.to_string() != "Arena" {
+        return Err(syn::Error::new(id.span(), "Identifier must be equals \"Arena\""));
+    }
+    input.parse::<Token![=]>()?;
+    let path = Path::parse_mod_style(input)?;
+    input.parse::<Token![;]>()?;
+    Ok(path)
+}
+
+/// Whether a `type Handle = ...;` directive follows.
+fn peek_smtype_handle_directive(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Token![type]>().is_ok() && fork.parse::<Ident>().is_ok_and(|id| id == "Handle")
+}
+
+fn parse_smtype_handle_kind(input: ParseStream) -> Result<HandleKind> {
+    input.parse::<Token![type]>()?;
+    input.parse::<Ident>()?;
+    input.parse::<Token![=]>()?;
+    let id = input.parse::<Ident>()?;
+    input.parse::<Token![;]>()?;
+    if id == "Weak" {
+        Ok(HandleKind::Weak)
+    } else if id == "Index" {
+        Ok(HandleKind::Index)
+    } else if id == "Sync" {
+        Ok(HandleKind::Sync)
+    } else {
+        Err(syn::Error::new(id.span(), "Handle must be either \"Weak\", \"Index\" or \"Sync\""))
+    }
+}
+
+#[proc_macro]
+pub fn sem(input: TokenStream) -> TokenStream {
+    let tree = parse_macro_input!(input as SmTypeTree);
+    process_smtype_tree(tree, false)
+}
+
+#[proc_macro]
+pub fn sem_extend(input: TokenStream) -> TokenStream {
+    let tree = parse_macro_input!(input as SmTypeTree);
+    process_smtype_tree(tree, true)
+}
+
+/// Processes the data types of a `sem!` invocation. If `is_extension` is true,
+/// a data type may inherit a data type defined by another invocation, referred to by path.
+fn process_smtype_tree(tree: SmTypeTree, is_extension: bool) -> TokenStream {
+    let SmTypeTree {
+        sem_path, arena_type_name, handle_kind, data_types
+    } = tree;
+
+    let mut host = SemHost::new();
+    host.sem_path = sem_path.clone();
+    host.handle_kind = handle_kind;
+
+    // # Validations
+
+    // 1. Ensure there is at least one data type.
+
+    if data_types.is_empty() {
+        host.error(Span::call_site(), "There must be at least one data type.");
+        return host.error_output();
+    }
+
+    // # Processing steps
+
+    // 1. Output the arena type, which holds the data of every root
+    // hierarchy, if a `type Arena = ArenaName1;` directive is given.
+    let runtime_arena_type = match handle_kind {
+        HandleKind::Weak => quote! { #sem_path::Arena },
+        HandleKind::Index => quote! { #sem_path::IndexArena },
+        HandleKind::Sync => quote! { #sem_path::sync::Arena },
+    };
+    let arena_type = if let Some(arena_type_name) = arena_type_name {
+        host.output.extend::<TokenStream>(quote! {
+            pub type #arena_type_name = #runtime_arena_type;
+        }.try_into().unwrap());
+        arena_type_name
+    } else {
+        runtime_arena_type
+    };
+
+    // 2. Traverse each type in a first pass.
+    for smtype_node in data_types.iter() {
+        ProcessingStep2().exec(&mut host, smtype_node);
+    }
+    if !host.errors.is_empty() {
+        return host.error_output();
+    }
+
+    // 2.1. Resolve the inherited bases, detecting inheritance cycles, and order
+    // the data types so that every base comes before its subtypes.
+    let Some(data_types) = ProcessingStep2_1().exec(&mut host, &data_types, is_extension) else {
+        return host.error_output();
+    };
+
+    // Derive the data module name from the first basemost data type,
+    // so that several invocations do not clash in the same Rust module.
+    host.data_module = format!("{DATA}{}", data_types[0].name);
+
+    // 3. Traverse each type in a second pass.
+    for smtype_node in data_types.iter() {
+        let Some(smtype) = host.semantics.get(smtype_node) else {
+            continue;
+        };
+
+        let asc_smtype_list = smtype.segment_asc_smtype_list();
+        let generics = smtype.generics();
+        let where_clause = &generics.where_clause;
+        let mut field_output = proc_macro2::TokenStream::new();
+        let smtype_name = smtype.name();
+
+        // 3.1. Write out the base data accessor
+
+        let base_accessor = base_accessor(&smtype);
+
+        // 3.2. Traverse each field.
+        for field in smtype_node.fields.iter() {
+            ProcessingStep3_2().exec(&mut host, &smtype, field, &base_accessor, &mut field_output);
+        }
+
+        // 3.3. Contribute a #DATA_BASE_FIELD field to #DATA::M holding the data
+        // of the inherited base, if it belongs to the same segment.
+        //
+        // Otherwise, M is the first data type of its segment; then contribute
+        // the #DATA_TAG_FIELD field to #DATA::M, and the #DATA_EXTENSION_FIELD
+        // field unless no data type of the segment is extensible.
+        if smtype.is_segment_root() {
+            let data_tag_field_id = Ident::new(DATA_TAG_FIELD, Span::call_site());
+            field_output.extend(quote! {
+                pub #data_tag_field_id: usize,
+            });
+            if smtype.segment_holds_extension() {
+                let any_bounds = host.any_bounds();
+                let data_extension_field_id = Ident::new(DATA_EXTENSION_FIELD, Span::call_site());
+                field_output.extend(quote! {
+                    pub #data_extension_field_id: ::std::option::Option<::std::boxed::Box<dyn #any_bounds>>,
+                });
+            }
+        } else {
+            let data_base_field_id = Ident::new(DATA_BASE_FIELD, Span::call_site());
+            let base_data_id = Ident::new(&format!("{DATA_PREFIX}{}", smtype.inherits().unwrap().name()), Span::call_site());
+            field_output.extend(quote! {
+                pub #data_base_field_id: #base_data_id,
+            });
+        }
+
+        // Contribute a #DATA_PHANTOM_FIELD field to #DATA::M holding
+        // the generic type parameters, if any.
+        let type_params = generics.type_params().map(|p| p.ident.clone()).collect::<Vec<_>>();
+        if !type_params.is_empty() {
+            let data_phantom_field_id = Ident::new(DATA_PHANTOM_FIELD, Span::call_site());
+            field_output.extend(quote! {
+                pub #data_phantom_field_id: ::std::marker::PhantomData<fn() -> (#(#type_params,)*)>,
+            });
+        }
+
+        let smtype_data_id = Ident::new(&format!("{DATA_PREFIX}{}", smtype_name), Span::call_site());
+
+        // 3.4. Define the data structure #DATA::M at the #DATA module output,
+        // containing all field output. An instance is allocated as the data
+        // of its most specific data type within each segment.
+        host.data_output.extend(quote! {
+            pub struct #smtype_data_id #generics #where_clause {
+                #field_output
+            }
+        });
+
+        // 3.5. If M is the first data type of its segment, define the segment trait at the
+        // #DATA module output, returning the data of each data type of the segment, and
+        // implement it for the data of M.
+        //
+        // Generic subtypes and subtypes of a generic data type start another segment;
+        // their data is held as extension data by the first data type of the segment.
+        let (impl_generics, ty_generics, _) = generics.split_for_impl();
+        if smtype.is_segment_root() {
+            let segment_trait_id = Ident::new(&format!("{DATA_SEGMENT_PREFIX}{smtype_name}"), Span::call_site());
+            let mut as_methods = proc_macro2::TokenStream::new();
+            for m in smtype.segment_smtype_list() {
+                let as_id = Ident::new(&format!("{DATA_AS_PREFIX}{}", m.name()), Span::call_site());
+                let m_data_id = Ident::new(&format!("{DATA_PREFIX}{}", m.name()), Span::call_site());
+                if m == smtype {
+                    as_methods.extend(quote! {
+                        fn #as_id(&self) -> &#m_data_id #ty_generics;
+                    });
+                } else {
+                    as_methods.extend(quote! {
+                        fn #as_id(&self) -> &#m_data_id {
+                            unreachable!()
+                        }
+                    });
+                }
+            }
+            let runtime_path = host.runtime_path();
+            host.data_output.extend(quote! {
+                pub trait #segment_trait_id #generics: #runtime_path::SegmentData #where_clause {
+                    #as_methods
+                }
+            });
+        }
+        let data_id = Ident::new(&host.data_module, Span::call_site());
+        let data_base_field_id = Ident::new(DATA_BASE_FIELD, Span::call_site());
+        let mut as_methods = proc_macro2::TokenStream::new();
+        let mut data = quote! { self };
+        for m in asc_smtype_list.iter().rev() {
+            let as_id = Ident::new(&format!("{DATA_AS_PREFIX}{}", m.name()), Span::call_site());
+            let m_data_id = Ident::new(&format!("{DATA_PREFIX}{}", m.name()), Span::call_site());
+            let m_generics = m.generics();
+            let (_, m_ty_generics, _) = m_generics.split_for_impl();
+            let data_ref = if m == &smtype { data.clone() } else { quote! { &#data } };
+            as_methods.extend(quote! {
+                fn #as_id(&self) -> &#data_id::#m_data_id #m_ty_generics {
+                    #data_ref
+                }
+            });
+            data = quote! { #data.#data_base_field_id };
+        }
+        let segment_trait = segment_trait(&smtype, &host.data_module);
+        host.output.extend::<TokenStream>(quote! {
+            impl #impl_generics #segment_trait for #data_id::#smtype_data_id #ty_generics #where_clause {
+                #as_methods
+            }
+        }.try_into().unwrap());
+
+        // 3.5.1. Implement `Trace` for #DATA::M, tracing the fields not marked with
+        // `#[sem(untraced)]`, then the data of the inherited base or, at the first data
+        // type of the segment, the extension data of a subtype through its vtable. The
+        // type of a traced field must implement `Trace`, which is reported at the type.
+        let runtime_path = host.runtime_path();
+        let trace_method = Ident::new(TRACE_METHOD, Span::call_site());
+        let mut trace_fields = proc_macro2::TokenStream::new();
+        for (name, field) in smtype.fields().borrow().iter() {
+            if field.is_untraced() {
+                continue;
+            }
+            let name_id = Ident::new(name, Span::call_site());
+            let cfg = cfg_attributes(&field.field_attributes());
+            let field_type = field.field_type();
+            let trace = quote_spanned! {field_type.span()=>
+                <#field_type as #runtime_path::Trace>::trace
+            };
+            let value = if field.is_ref() {
+                quote! { &*self.#name_id.borrow() }
+            } else {
+                quote! { &self.#name_id.get() }
+            };
+            trace_fields.extend(quote! {
+                #(#cfg)*
+                #trace(#value, tracer);
+            });
+        }
+        if smtype.is_segment_root() {
+            if smtype.segment_holds_extension() {
+                let data_extension_field_id = Ident::new(DATA_EXTENSION_FIELD, Span::call_site());
+                let vtable_name = Ident::new(&format!("{VTABLE_PREFIX}{smtype_name}"), Span::call_site());
+                trace_fields.extend(quote! {
+                    if let Some(ext) = self.#data_extension_field_id.as_ref().and_then(|ext| ext.downcast_ref::<#runtime_path::Extension<dyn #vtable_name #ty_generics>>()) {
+                        ext.vtable.#trace_method(&*ext.data, tracer);
+                    }
+                });
+            }
+        } else {
+            trace_fields.extend(quote! {
+                #runtime_path::Trace::trace(&self.#data_base_field_id, tracer);
+            });
+        }
+        host.output.extend::<TokenStream>(quote! {
+            impl #impl_generics #runtime_path::Trace for #data_id::#smtype_data_id #ty_generics #where_clause {
+                fn trace(&self, tracer: &mut #runtime_path::Tracer) {
+                    #trace_fields
+                }
+            }
+        }.try_into().unwrap());
+
+        // 3.6. Define the structure M
+        ProcessingStep3_6().exec(&mut host, &smtype_node, &smtype, &base_accessor, &sem_path);
+
+        // 3.7. Define the constructor
+        ProcessingStep3_7().exec(&mut host, smtype_node, &smtype, &asc_smtype_list, &arena_type);
+
+        // 3.8. Traverse each method
+        for method in smtype_node.methods.iter() {
+            ProcessingStep3_8().exec(&mut host, method, &smtype);
+        }
+    }
+    if !host.errors.is_empty() {
+        return host.error_output();
+    }
+
+    // Ensure every non-abstract data type overrides
+    // every abstract method it inherits.
+    for smtype_node in data_types.iter() {
+        let Some(smtype) = host.semantics.get(smtype_node) else {
+            continue;
+        };
+        if smtype.is_abstract() {
+            continue;
+        }
+        for m in smtype.asc_smtype_list().iter() {
+            let mut method_names = m.methods().borrow().keys().cloned().collect::<Vec<_>>();
+            method_names.sort();
+            for method_name in method_names {
+                let method = smtype.lookup_method(&method_name).unwrap();
+                if method.is_abstract() && method.defined_in() == *m {
+                    host.error(smtype_node.name.span(), format!("Data type '{}' must override abstract method '{}' of '{}'.", smtype.name(), method_name, m.name()));
+                }
+            }
+        }
+    }
+    if !host.errors.is_empty() {
+        return host.error_output();
+    }
+
+    // 4. Traverse each type in a third pass.
+    for smtype_node in data_types.iter() {
+        let Some(smtype) = host.semantics.get(smtype_node) else {
+            continue;
+        };
+
+        let smtype_name = smtype.name();
+        let smtype_name_id = Ident::new(&smtype_name, Span::call_site());
+        let generics = smtype.generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        // 4.1. Traverse each method, including the ones implicitly overriden
+        let implicit_overrides = host.implicit_overrides.get(&smtype).cloned().unwrap_or_default();
+        for method in smtype_node.methods.iter().chain(implicit_overrides.iter()) {
+            ProcessingStep4_1().exec(&mut host, method, &smtype);
+        }
+
+        // Contribute the hidden `#TYPE_NAME_METHOD` method
+        ProcessingStep4_1().define_type_name(&host, &smtype_node.visibility, &smtype);
+
+        // 4.2. Define the vtable trait
+        ProcessingStep4_2().exec(&mut host, smtype_node, &smtype, &base_accessor(&smtype));
+
+        // * Contribute a `to::<T>()` method, where `T` is any data type of the hierarchy.
+        // * Contribute an `is::<T>()` method.
+        // * Contribute an `is_alive()` method.
+        let try_data = try_data_accessor(&host, &smtype, &base_accessor(&smtype));
+        smtype.method_output().borrow_mut().extend(quote! {
+            pub fn to<__T: #sem_path::DataType<Root = <Self as #sem_path::DataType>::Root>>(&self) -> Result<__T, #sem_path::SemError> {
+                __T::from_root(#sem_path::DataType::into_root(self.clone()))
+                    .map_err(|e| e.with_source(#smtype_name))
+            }
+            pub fn is<__T: #sem_path::DataType<Root = <Self as #sem_path::DataType>::Root>>(&self) -> bool {
+                self.to::<__T>().is_ok()
+            }
+            pub fn is_alive(&self) -> bool {
+                #try_data.is_ok()
+            }
+        });
+
+        let method_output = smtype.method_output().borrow().clone();
+
+        // Output the code of all methods to an `impl` block for the data type.
+        host.output.extend::<TokenStream>(quote! {
+            impl #impl_generics #smtype_name_id #ty_generics #where_clause {
+                #method_output
+            }
+        }.try_into().unwrap());
+    }
+
+    let data_output = std::mem::take(&mut host.data_output);
+    let data_id = Ident::new(&host.data_module, Span::call_site());
+
+    // 5. Output the `mod #DATA { use super::*; ... }` module with its respective contents
+    host.output.extend::<TokenStream>(quote! {
+        #[allow(non_camel_case_types, non_snake_case)]
+        mod #data_id {
+            use super::*;
+
+            #data_output
+        }
+    }.try_into().unwrap());
+
+    // 5. Return output, or only the errors reported by the third pass,
+    // so that they are not followed by errors in the generated code.
+    if !host.errors.is_empty() {
+        return host.error_output();
+    }
+    host.output
+}
+
+/// Returns the base data accessor of a data type: a `Weak<dyn #DATA::#DATA_SEGMENT_PREFIX FirstM>`
+/// or `Index<dyn #DATA::#DATA_SEGMENT_PREFIX FirstM>` value,
+/// or the segment base value if the data type's segment has a base.
+///
+/// For example, for the basemost data type, this
+/// is always `self.0`; for a direct subtype of the basemost
+/// data type, this is always `self.0.0`.
+fn base_accessor(smtype: &Symbol) -> proc_macro2::TokenStream {
+    let mut base_accessor = quote! { self.0 };
+    let mut m1 = smtype.clone();
+    while !m1.is_segment_root() {
+        base_accessor = quote! { #base_accessor.0 };
+        m1 = m1.inherits().unwrap();
+    }
+    base_accessor
+}
+
+/// Returns an expression evaluating to the `Rc<dyn #DATA::#DATA_SEGMENT_PREFIX FirstM>`
+/// (or `Arc<...>`) data of the segment of `smtype`, given the base data accessor,
+/// which panics with the `SemError::Dropped` message if the entity has been dropped.
+fn data_accessor(host: &SemHost, smtype: &Symbol, base_accessor: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let try_data = try_data_accessor(host, smtype, base_accessor);
+    quote! { #try_data.unwrap_or_else(|e| panic!("{e}")) }
+}
+
+/// Returns an expression evaluating to a `Result` of the data of the segment
+/// of `smtype`, as returned by `data_accessor`, which fails with `SemError::Dropped`
+/// if the entity has been dropped.
+fn try_data_accessor(host: &SemHost, smtype: &Symbol, base_accessor: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let sem_path = &host.sem_path;
+    let smtype_name = smtype.name();
+    if smtype.segment_base().is_some() {
+        let extension_data_method = Ident::new(EXTENSION_DATA_METHOD, Span::call_site());
+        let rc_path = host.rc_path();
+        let segment_trait = segment_trait(smtype, &host.data_module);
+        quote! { #base_accessor.#extension_data_method::<#rc_path<dyn #segment_trait>>().ok_or(#sem_path::SemError::Dropped { data_type: #smtype_name }) }
+    } else {
+        quote! { #base_accessor.upgrade().ok_or(#sem_path::SemError::Dropped { data_type: #smtype_name }) }
+    }
+}
+
+/// Returns the `#DATA::#DATA_SEGMENT_PREFIX FirstM` trait implemented by the data of
+/// the data types of the segment of `smtype`, with its generic arguments.
+fn segment_trait(smtype: &Symbol, data_module: &str) -> proc_macro2::TokenStream {
+    let first = &smtype.segment_asc_smtype_list()[0];
+    let generics = first.generics();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let data_id = Ident::new(data_module, Span::call_site());
+    let segment_trait_id = Ident::new(&format!("{DATA_SEGMENT_PREFIX}{}", first.name()), Span::call_site());
+    quote! { #data_id::#segment_trait_id #ty_generics }
+}
+
+/// Returns an expression referencing the `#DATA::M` data of `smtype`, given
+/// the data of its segment.
+fn data_of(smtype: &Symbol, segment_data: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let as_id = Ident::new(&format!("{DATA_AS_PREFIX}{}", smtype.name()), Span::call_site());
+    quote! { #segment_data.#as_id() }
+}
+
+/// Returns the `#[cfg]` attributes among `attributes`.
+fn cfg_attributes(attributes: &[Attribute]) -> Vec<Attribute> {
+    attributes.iter().filter(|a| a.path().is_ident("cfg")).cloned().collect()
+}
+
+/// Names of the attributes that apply to type definitions only, and
+/// therefore not to the accessors of a field.
+const TYPE_ATTRIBUTES: [&str; 2] = ["derive", "repr"];
+
+/// Whether a field attribute applies to the accessors of the field.
+fn is_accessor_attribute(attribute: &Attribute) -> bool {
+    !TYPE_ATTRIBUTES.iter().any(|name| attribute.path().is_ident(name))
+}
+
+/// Substitutes the generic parameters of a data type, given by `generics`,
+/// by the generic `arguments` given to the data type, in a token sequence.
+fn substitute_generics(tokens: proc_macro2::TokenStream, generics: &Generics, arguments: &PathArguments) -> proc_macro2::TokenStream {
+    let PathArguments::AngleBracketed(arguments) = arguments else {
+        return tokens;
+    };
+    let params = generics.params.iter().filter_map(|p| match p {
+        GenericParam::Type(p) => Some(p.ident.to_string()),
+        GenericParam::Const(p) => Some(p.ident.to_string()),
+        GenericParam::Lifetime(_) => None,
+    });
+    let arguments = arguments.args.iter().filter(|a| !matches!(a, GenericArgument::Lifetime(_)));
+    let mapping = params.zip(arguments.map(|a| a.to_token_stream())).collect::<HashMap<_, _>>();
+    substitute_tokens(tokens, &mapping)
+}
+
+fn substitute_tokens(tokens: proc_macro2::TokenStream, mapping: &HashMap<String, proc_macro2::TokenStream>) -> proc_macro2::TokenStream {
+    let mut output = proc_macro2::TokenStream::new();
+    for token in tokens {
+        match &token {
+            proc_macro2::TokenTree::Ident(id) if mapping.contains_key(&id.to_string()) => {
+                output.extend(mapping[&id.to_string()].clone());
+            },
+            proc_macro2::TokenTree::Group(g) => {
+                let mut g1 = proc_macro2::Group::new(g.delimiter(), substitute_tokens(g.stream(), mapping));
+                g1.set_span(g.span());
+                output.extend([proc_macro2::TokenTree::Group(g1)]);
+            },
+            _ => {
+                output.extend([token.clone()]);
+            },
+        }
+    }
+    output
+}
+
+/// Returns the signature of an instance method, as seen from `smtype`: the generic
+/// parameters of the data type defining it are substituted in terms of those of `smtype`.
+fn signature_at(smtype: &Symbol, method: &Symbol) -> Rc<SmTypeMethod> {
+    let signature = method.signature();
+    let defined_in = method.defined_in();
+    if defined_in == *smtype || !defined_in.is_generic() {
+        return signature;
+    }
+    let inputs = smtype.substitute_ancestor_generics(&defined_in, signature.inputs.to_token_stream());
+    let result_type = signature.result_type.as_ref().map(|t| smtype.substitute_ancestor_generics(&defined_in, t.to_token_stream()));
+    let where_clause = signature.generics.where_clause.as_ref().map(|c| smtype.substitute_ancestor_generics(&defined_in, c.to_token_stream()));
+    let mut generics = signature.generics.clone();
+    generics.where_clause = where_clause.map(|c| syn::parse2(c).unwrap());
+    Rc::new(SmTypeMethod {
+        attributes: RefCell::new(vec![]),
+        visibility: signature.visibility.clone(),
+        is_override: signature.is_override,
+        is_abstract: signature.is_abstract,
+        name: signature.name.clone(),
+        generics,
+        inputs: Punctuated::<FnArg, Comma>::parse_terminated.parse2(inputs).unwrap(),
+        result_type: result_type.map(|t| syn::parse2(t).unwrap()),
+        statements: proc_macro2::TokenStream::new(),
+    })
+}
+
+/// Binds the parameters of a method by position, so that they are forwarded to
+/// another method regardless of their names. Returns the parameters, where one not
+/// bound to a plain identifier, such as `mut x` or `(a, b)`, is instead bound to
+/// `ARG_PREFIX` followed by its position, and the arguments forwarding them.
+fn bind_inputs_by_position(host: &mut SemHost, input: &Punctuated<FnArg, Comma>) -> (Punctuated<FnArg, Comma>, Punctuated<proc_macro2::TokenStream, Comma>) {
+    let mut inputs = Punctuated::<FnArg, Comma>::new();
+    let mut args = Punctuated::<proc_macro2::TokenStream, Comma>::new();
+    for (i, arg) in input.iter().enumerate() {
+        match arg {
+            FnArg::Receiver(_) => {
+                host.error_spanned(arg, "Unexpected receiver.");
+            },
+            FnArg::Typed(pt) => {
+                let mut pt = pt.clone();
+                let id = match pt.pat.as_ref() {
+                    Pat::Ident(p) if p.by_ref.is_none() && p.mutability.is_none() && p.subpat.is_none() => p.ident.clone(),
+                    _ => {
+                        let id = Ident::new(&format!("{ARG_PREFIX}{i}"), pt.pat.span());
+                        pt.pat = Box::new(Pat::Ident(PatIdent { attrs: vec![], by_ref: None, mutability: None, ident: id.clone(), subpat: None }));
+                        id
+                    },
+                };
+                args.push(id.to_token_stream());
+                inputs.push(FnArg::Typed(pt));
+            },
+        }
+    }
+    (inputs, args)
+}
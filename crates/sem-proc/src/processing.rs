@@ -1,20 +1,26 @@
-mod sem_host;
-pub use sem_host::*;
-
-mod processing_step_2;
-pub use processing_step_2::*;
-
-mod processing_step_3_2;
-pub use processing_step_3_2::*;
-
-mod processing_step_3_6;
-pub use processing_step_3_6::*;
-
-mod processing_step_3_7;
-pub use processing_step_3_7::*;
-
-mod processing_step_3_8;
-pub use processing_step_3_8::*;
-
-mod processing_step_4_1;
-pub use processing_step_4_1::*;
\ No newline at end of file
+mod sem_host;
+pub use sem_host::*;
+
+mod processing_step_2;
+pub use processing_step_2::*;
+
+mod processing_step_2_1;
+pub use processing_step_2_1::*;
+
+mod processing_step_3_2;
+pub use processing_step_3_2::*;
+
+mod processing_step_3_6;
+pub use processing_step_3_6::*;
+
+mod processing_step_3_7;
+pub use processing_step_3_7::*;
+
+mod processing_step_3_8;
+pub use processing_step_3_8::*;
+
+mod processing_step_4_1;
+pub use processing_step_4_1::*;
+
+mod processing_step_4_2;
+pub use processing_step_4_2::*;
\ No newline at end of file
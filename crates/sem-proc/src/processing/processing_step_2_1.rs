@@ -0,0 +1,110 @@
+use crate::*;
+
+pub struct ProcessingStep2_1();
+
+impl ProcessingStep2_1 {
+    /// Resolves the inherited base of every data type, reports inheritance cycles
+    /// and returns the data types ordered so that every base comes before its subtypes.
+    ///
+    /// If `is_extension` is true, a base that is not defined by the invocation
+    /// resolves to an external data type.
+    pub fn exec(&self, host: &mut SemHost, data_types: &[Rc<SmType>], is_extension: bool) -> Option<Vec<Rc<SmType>>> {
+        let mut valid = true;
+
+        // 1. Resolve the inherited base of each data type.
+        // 1.1. If the inherited base failed to resolve, report an error;
+        // otherwise contribute the type to the inherited base's list of subtypes.
+        for m in data_types.iter() {
+            let (Some(slot), Some(inherits)) = (host.semantics.get(m), &m.inherits) else {
+                continue;
+            };
+            let inherits_name = inherits.to_token_stream().to_string().replace(' ', "");
+            let local_smtype = if inherits.leading_colon.is_none() && inherits.segments.len() == 1 {
+                host.smtype_slots.get(&inherits.segments[0].ident.to_string()).cloned()
+            } else {
+                None
+            };
+            let inherited_smtype = if let Some(local_smtype) = local_smtype {
+                local_smtype
+            } else if is_extension {
+                host.external_smtype_slots.entry(inherits_name)
+                    .or_insert_with(|| host.factory.create_external_smtype_slot(inherits.clone()))
+                    .clone()
+            } else {
+                host.error_spanned(inherits, format!("Data type '{}' not found.", inherits_name));
+                valid = false;
+                continue;
+            };
+            slot.set_inherits(Some(&inherited_smtype));
+            slot.set_inherits_path(Some(inherits.clone()));
+            inherited_smtype.subtypes().push(slot.clone());
+        }
+        if !valid {
+            return None;
+        }
+
+        // 2. Detect inheritance cycles by walking the chain of bases of each
+        // data type. Each cycle is reported once, at the data type of the cycle
+        // that is defined first.
+        let mut visited = HashSet::<Symbol>::new();
+        for m in data_types.iter() {
+            let mut path: Vec<Symbol> = vec![];
+            let mut m1 = host.semantics.get(m);
+            while let Some(m2) = m1 {
+                if visited.contains(&m2) {
+                    break;
+                }
+                if let Some(i) = path.iter().position(|m3| m3 == &m2) {
+                    self.report_cycle(host, data_types, &path[i..]);
+                    valid = false;
+                    break;
+                }
+                path.push(m2.clone());
+                m1 = m2.inherits();
+            }
+            visited.extend(path);
+        }
+        if !valid {
+            return None;
+        }
+
+        // 3. Order the data types, starting from the basemost ones and
+        // descending into their subtypes.
+        let nodes = data_types.iter()
+            .filter_map(|m| host.semantics.get(m).map(|slot| (slot, m.clone())))
+            .collect::<HashMap<Symbol, Rc<SmType>>>();
+        let mut ordered = vec![];
+        for m in data_types.iter() {
+            let slot = host.semantics.get(m).unwrap();
+            if slot.inherits().map(|m1| m1.is_external()).unwrap_or(true) {
+                self.order(&slot, &nodes, &mut ordered);
+            }
+        }
+        Some(ordered)
+    }
+
+    fn order(&self, smtype: &Symbol, nodes: &HashMap<Symbol, Rc<SmType>>, ordered: &mut Vec<Rc<SmType>>) {
+        ordered.push(nodes[smtype].clone());
+        for subtype in smtype.subtypes().iter() {
+            self.order(&subtype, nodes, ordered);
+        }
+    }
+
+    fn report_cycle(&self, host: &mut SemHost, data_types: &[Rc<SmType>], cycle: &[Symbol]) {
+        // Start the cycle at the data type that is defined first.
+        let Some((node, first)) = data_types.iter().find_map(|m| {
+            let slot = host.semantics.get(m)?;
+            cycle.contains(&slot).then_some((m, slot))
+        }) else {
+            return;
+        };
+        let mut names = vec![first.name()];
+        let mut m = first.inherits().unwrap();
+        while m != first {
+            names.push(m.name());
+            m = m.inherits().unwrap();
+        }
+        names.push(first.name());
+        host.error(node.name.span(), format!("Inheritance cycle: {}.", names.join(" -> ")));
+    }
+}
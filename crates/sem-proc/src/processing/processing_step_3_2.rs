@@ -1,85 +1,214 @@
-use crate::*;
-
-pub struct ProcessingStep3_2();
-
-impl ProcessingStep3_2 {
-    pub fn exec(&self, host: &mut SemHost, smtype: &Symbol, field: &Rc<SmTypeField>, base_accessor: &str, asc_smtype_list: &[Symbol], field_output: &mut proc_macro2::TokenStream) -> bool {
-        // 1. Create a FieldSlot.
-        let slot = host.factory.create_field_slot(field.is_ref, field.name.to_string(), field.type_annotation.clone(), field.default_value.clone());
-
-        // 2. Contribute the field slot to the type slot.
-        if smtype.fields().has(&slot.name()) {
-            field.name.span().unwrap().error(format!("Redefining '{}'", slot.name())).emit();
-            return false;
-        } else {
-            smtype.fields().set(slot.name(), slot.clone());
-        }
-
-        // 3. Contribute a field to the #DATA::M structure.
-        let field_name = slot.name();
-        let field_name_id = Ident::new(&field_name, Span::call_site());
-        let field_type = slot.field_type();
-        if slot.is_ref() {
-            field_output.extend(quote! {
-                pub #field_name_id: ::std::cell::RefCell<#field_type>,
-            });
-        } else {
-            field_output.extend(quote! {
-                pub #field_name_id: ::std::cell::Cell<#field_type>,
-            });
-        }
-
-        // 4. Define accessors
-        self.define_accessors(host, smtype, &slot, &field_name, &field_type, base_accessor, asc_smtype_list);
-
-        true
-    }
-
-    fn define_accessors(&self, _host: &mut SemHost, smtype: &Symbol, slot: &Symbol, field_name: &str, field_type: &Type, base_accessor: &str, asc_smtype_list: &[Symbol]) {
-        let getter_name = Ident::new(&field_name, Span::call_site());
-        let setter_name = Ident::new(&format!("set_{}", field_name), Span::call_site());
-        let fv = proc_macro2::TokenStream::from_str(&self.match_field(asc_smtype_list, 0, &format!("{base_accessor}.upgrade().unwrap()"), field_name)).unwrap();
-
-        if slot.is_ref() {
-            smtype.method_output().borrow_mut().extend(quote! {
-                #[allow(non_snake_case)]
-                fn #getter_name(&self) -> #field_type {
-                    #fv.borrow().clone()
-                }
-                #[allow(non_snake_case)]
-                fn #setter_name(&self, v: #field_type) {
-                    #fv.replace(v);
-                }
-            });
-        } else {
-            smtype.method_output().borrow_mut().extend(quote! {
-                #[allow(non_snake_case)]
-                fn #getter_name(&self) -> #field_type {
-                    #fv.get()
-                }
-
-                #[allow(non_snake_case)]
-                fn #setter_name(&self, v: #field_type) {
-                    #fv.set(v);
-                }
-            });
-        }
-    }
-
-    /// Matches a field. `base` is assumed to be a `Rc<#DATA::M>` value.
-    fn match_field(&self, asc_smtype_list: &[Symbol], smtype_index: usize, base: &str, field_name: &str) -> String {
-        let (smtype, inherited) = if smtype_index + 1 >= asc_smtype_list.len() {
-            (asc_smtype_list[smtype_index].clone(), None)
-        } else {
-            (asc_smtype_list[smtype_index + 1].clone(), Some(asc_smtype_list[smtype_index].clone()))
-        };
-
-        let Some(inherited) = inherited else {
-            return format!("(&{}.{})", base, field_name);
-        };
-        format!("(if let {DATA}::{}::{}(o) = &{base}.{DATA_VARIANT_FIELD} {{ {} }} else {{ panic!() }})",
-            DATA_VARIANT_PREFIX.to_owned() + &inherited.name(),
-            DATA_PREFIX.to_owned() + &smtype.name(),
-            self.match_field(asc_smtype_list, smtype_index + 1, "o", field_name))
-    }
+use crate::*;
+
+pub struct ProcessingStep3_2();
+
+impl ProcessingStep3_2 {
+    pub fn exec(&self, host: &mut SemHost, smtype: &Symbol, field: &Rc<SmTypeField>, base_accessor: &proc_macro2::TokenStream, field_output: &mut proc_macro2::TokenStream) -> bool {
+        // 1. Create a FieldSlot.
+        let slot = host.factory.create_field_slot(field.is_ref, field.is_untraced, field.name.to_string(), field.type_annotation.clone(), field.default_value.clone(), field.attributes.clone());
+
+        // 2. Contribute the field slot to the type slot.
+        if smtype.fields().has(&slot.name()) {
+            host.error(field.name.span(), format!("Redefining '{}'", slot.name()));
+            return false;
+        } else {
+            smtype.fields().set(slot.name(), slot.clone());
+        }
+
+        // `#[derive]` and `#[repr]` apply to type definitions, which the
+        // accessors are not.
+        for attr in field.attributes.iter().filter(|a| !is_accessor_attribute(a)) {
+            host.error_spanned(attr, "Unsupported field attribute: `derive` and `repr` apply to type definitions, not to the accessors of a field.");
+        }
+
+        // 3. Contribute a field to the #DATA::M structure.
+        let field_name = slot.name();
+        let field_name_id = Ident::new(&field_name, Span::call_site());
+        let field_type = slot.field_type();
+        let cfg = cfg_attributes(&field.attributes);
+        let cell_path = host.cell_path(slot.is_ref());
+        field_output.extend(quote! {
+            #(#cfg)*
+            pub #field_name_id: #cell_path<#field_type>,
+        });
+
+        // 4. Define accessors
+        self.define_accessors(host, smtype, field, &slot, base_accessor);
+
+        true
+    }
+
+    fn define_accessors(&self, host: &mut SemHost, smtype: &Symbol, field: &SmTypeField, slot: &Symbol, base_accessor: &proc_macro2::TokenStream) {
+        let field_name = slot.name();
+        let field_type = slot.field_type();
+        let sem_path = &host.sem_path;
+        let runtime_path = host.runtime_path();
+        let getter_vis = field.visibility.clone();
+        let setter_vis = field.setter_visibility.clone();
+        // Attributes, including doc comments and `#[cfg]`, apply to every accessor.
+        let attrs = field.attributes.iter().filter(|a| is_accessor_attribute(a)).collect::<Vec<_>>();
+        // The accessors span the field name, so that their uses lead to the field.
+        let span = field.name.span();
+        let getter_name = Ident::new(&field_name, span);
+        let setter_name = Ident::new(&format!("set_{}", field_name), span);
+        let replace_name = Ident::new(&format!("replace_{}", field_name), span);
+        let take_name = Ident::new(&format!("take_{}", field_name), span);
+        let update_name = Ident::new(&format!("update_{}", field_name), span);
+        let reset_name = Ident::new(&format!("reset_{}", field_name), span);
+        let try_getter_name = Ident::new(&format!("try_{}", field_name), span);
+        let try_setter_name = Ident::new(&format!("try_set_{}", field_name), span);
+        let field_init = slot.field_init();
+        let field_name_id = Ident::new(&field_name, Span::call_site());
+        let data = data_accessor(host, smtype, base_accessor);
+        let fv = data_of(smtype, &data);
+        let fv = quote! { (&#fv.#field_name_id) };
+
+        // The `try_` accessors fail with `SemError::Dropped` rather than panic
+        // if the entity has been dropped.
+        let try_data = try_data_accessor(host, smtype, base_accessor);
+        let try_fv = data_of(smtype, &quote! { __data });
+        let try_fv = quote! { (&#try_fv.#field_name_id) };
+
+        if slot.is_ref() {
+            let ref_name = Ident::new(&format!("{}_ref", field_name), span);
+            let with_name = Ident::new(&format!("with_{}", field_name), span);
+            let with_mut_name = Ident::new(&format!("with_{}_mut", field_name), span);
+            let try_ref_name = Ident::new(&format!("try_{}_ref", field_name), span);
+            let projected_fv = data_of(smtype, &quote! { data });
+            let projected_fv = quote! { (&#projected_fv.#field_name_id) };
+
+            // The getter is bounded through a higher-ranked clause so that
+            // it does not fail for a non-Clone field type.
+            smtype.method_output().borrow_mut().extend(quote! {
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #getter_vis fn #getter_name(&self) -> #field_type where for<'__a> #field_type: ::std::clone::Clone {
+                    #fv.borrow().clone()
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #getter_vis fn #ref_name(&self) -> #runtime_path::FieldRef<'_, #field_type> {
+                    #runtime_path::FieldRef::new(#data, |data| #projected_fv)
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #getter_vis fn #with_name<__R>(&self, f: impl FnOnce(&#field_type) -> __R) -> __R {
+                    f(&#fv.borrow())
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #setter_name(&self, v: #field_type) {
+                    *#fv.borrow_mut() = v;
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #with_mut_name<__R>(&self, f: impl FnOnce(&mut #field_type) -> __R) -> __R {
+                    f(&mut #fv.borrow_mut())
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #replace_name(&self, v: #field_type) -> #field_type {
+                    #fv.replace(v)
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #take_name(&self) -> #field_type where for<'__a> #field_type: ::std::default::Default {
+                    #fv.take()
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #update_name(&self, f: impl FnOnce(&mut #field_type)) {
+                    f(&mut #fv.borrow_mut());
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #getter_vis fn #try_getter_name(&self) -> ::std::result::Result<#field_type, #sem_path::SemError> where for<'__a> #field_type: ::std::clone::Clone {
+                    let __data = #try_data?;
+                    let v = #try_fv.borrow().clone();
+                    Ok(v)
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #getter_vis fn #try_ref_name(&self) -> ::std::result::Result<#runtime_path::FieldRef<'_, #field_type>, #sem_path::SemError> {
+                    Ok(#runtime_path::FieldRef::new(#try_data?, |data| #projected_fv))
+                }
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #try_setter_name(&self, v: #field_type) -> ::std::result::Result<(), #sem_path::SemError> {
+                    let __data = #try_data?;
+                    *#try_fv.borrow_mut() = v;
+                    Ok(())
+                }
+            });
+        } else {
+            // With locked fields, the update holds the lock throughout,
+            // so that concurrent updates are not lost.
+            let update = if host.handle_kind == HandleKind::Sync {
+                quote! { #fv.update(f); }
+            } else {
+                quote! {
+                    let mut v = #fv.get();
+                    f(&mut v);
+                    #fv.set(v);
+                }
+            };
+            smtype.method_output().borrow_mut().extend(quote! {
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #getter_vis fn #getter_name(&self) -> #field_type {
+                    #fv.get()
+                }
+
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #setter_name(&self, v: #field_type) {
+                    #fv.set(v);
+                }
+
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #replace_name(&self, v: #field_type) -> #field_type {
+                    #fv.replace(v)
+                }
+
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #take_name(&self) -> #field_type where for<'__a> #field_type: ::std::default::Default {
+                    #fv.take()
+                }
+
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #update_name(&self, f: impl FnOnce(&mut #field_type)) {
+                    #update
+                }
+
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #getter_vis fn #try_getter_name(&self) -> ::std::result::Result<#field_type, #sem_path::SemError> {
+                    let __data = #try_data?;
+                    Ok(#try_fv.get())
+                }
+
+                #(#attrs)*
+                #[allow(non_snake_case)]
+                #setter_vis fn #try_setter_name(&self, v: #field_type) -> ::std::result::Result<(), #sem_path::SemError> {
+                    let __data = #try_data?;
+                    #try_fv.set(v);
+                    Ok(())
+                }
+            });
+        }
+
+        // Restores the declared default value. The setter may be deprecated
+        // or `#[must_use]` along with the field.
+        smtype.method_output().borrow_mut().extend(quote! {
+            #(#attrs)*
+            #[allow(non_snake_case)]
+            #setter_vis fn #reset_name(&self) {
+                #[allow(deprecated, unused_must_use)]
+                self.#setter_name(#field_init);
+            }
+        });
+    }
 }
\ No newline at end of file
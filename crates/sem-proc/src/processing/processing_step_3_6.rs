@@ -1,136 +1,388 @@
-use crate::*;
-
-pub struct ProcessingStep3_6();
-
-impl ProcessingStep3_6 {
-    pub fn exec(&self, host: &mut SemHost, node: &Rc<SmType>, smtype: &Symbol, base_accessor: &str, sem_path: &proc_macro2::TokenStream) {
-        let smtype_name_debug = format!("{}()", smtype.name());
-        let smtype_name = node.name.clone();
-        let attributes = node.attributes.clone();
-        let visi = node.visibility.clone();
-
-        // Define the structure M, as in
-        //
-        // ```
-        // #[derive(Clone)]
-        // struct M(Weak<#DATA::M>);
-        // ```
-        //
-        // or as in:
-        //
-        // ```
-        // #[derive(Clone, PartialEq, Hash)]
-        // struct M(InheritedM);
-        // ```
-        //
-        // if there is an inherited base.
-        if let Some(inherits) = smtype.inherits() {
-            let inherited_name = Ident::new(&inherits.name(), Span::call_site());
-            host.output.extend::<TokenStream>(quote! {
-                #(#attributes)*
-                #[derive(Clone, PartialEq, Hash)]
-                #visi struct #smtype_name(#inherited_name);
-
-                impl ::std::ops::Deref for #smtype_name {
-                    type Target = #inherited_name;
-                    fn deref(&self) -> &Self::Target {
-                        &self.0
-                    }
-                }
-            }.try_into().unwrap());
-        } else {
-            let data_id = Ident::new(DATA, Span::call_site());
-            let smtype_data_name = Ident::new(&format!("{DATA_PREFIX}{}", smtype.name()), Span::call_site());
-            host.output.extend::<TokenStream>(quote! {
-                #(#attributes)*
-                #[derive(Clone)]
-                #visi struct #smtype_name(::std::rc::Weak<#data_id::#smtype_data_name>);
-
-                impl PartialEq for #smtype_name {
-                    fn eq(&self, other: &Self) -> bool {
-                        self.0.ptr_eq(&other.0)
-                    }
-                }
-
-                impl ::std::hash::Hash for #smtype_name {
-                    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
-                        self.0.as_ptr().hash(state)
-                    }
-                }
-            }.try_into().unwrap());
-        }
-
-        // Implement Eq and Debug
-        host.output.extend::<TokenStream>(quote! {
-            impl Eq for #smtype_name {}
-
-            impl ::std::fmt::Debug for #smtype_name {
-                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-                    write!(f, #smtype_name_debug)
-                }
-            }
-        }.try_into().unwrap());
-
-        // Output From<M> for InheritedM implementation (covariant conversion)
-        let mut base = "v.0.0".to_owned();
-        let mut m = smtype.clone();
-        while let Some(m1) = m.inherits() {
-            let inherited_name = Ident::new(&m1.name(), Span::call_site());
-            let base_tokens = proc_macro2::TokenStream::from_str(&base).unwrap();
-            host.output.extend::<TokenStream>(quote! {
-                impl From<#smtype_name> for #inherited_name {
-                    fn from(v: #smtype_name) -> Self {
-                        #inherited_name(#base_tokens.clone())
-                    }
-                }
-            }.try_into().unwrap());
-            m = m1;
-            base = format!("{base}.0");
-        }
-
-        // Output a TryFrom<M> for SubtypeM implementation (contravariant conversion)
-        for sm in smtype.subtypes().iter() {
-            self.contravariance(host, &base_accessor.replacen("self", "v", 1), smtype, &sm, sem_path);
-        }
-    }
-
-    fn contravariance(&self, host: &mut SemHost, base_accessor: &str, base_smtype: &Symbol, subtype: &Symbol, sem_path: &proc_macro2::TokenStream) {
-        let base_smtype_name = Ident::new(&base_smtype.name(), Span::call_site());
-        let subtype_name = Ident::new(&subtype.name(), Span::call_site());
-        let m = proc_macro2::TokenStream::from_str(&self.match_contravariant(&subtype.asc_smtype_list(), 0, &format!("{base_accessor}.upgrade().unwrap()"), &base_accessor, sem_path)).unwrap();
-
-        host.output.extend::<TokenStream>(quote! {
-            impl TryFrom<#base_smtype_name> for #subtype_name {
-                type Error = #sem_path::SemError;
-                fn try_from(v: #base_smtype_name) -> Result<Self, Self::Error> {
-                    #m
-                }
-            }
-        }.try_into().unwrap());
-
-        for sm1 in subtype.subtypes().iter() {
-            self.contravariance(host, base_accessor, base_smtype, &sm1, sem_path);
-        }
-    }
-
-    /// Matches a contravariant type.
-    /// 
-    /// * `base` is assumed to be a `Rc<#DATA::M>` value.
-    /// * `original_base` is assumed to be a `Weak<#DATA::FirstM>` value.
-    fn match_contravariant(&self, asc_smtype_list: &[Symbol], smtype_index: usize, base: &str, original_base: &str, sem_path: &proc_macro2::TokenStream) -> String {
-        let (smtype, inherited) = if smtype_index + 1 >= asc_smtype_list.len() {
-            (asc_smtype_list[smtype_index].clone(), None)
-        } else {
-            (asc_smtype_list[smtype_index + 1].clone(), Some(asc_smtype_list[smtype_index].clone()))
-        };
-
-        let Some(inherited) = inherited else {
-            return format!("Ok({})", Symbol::create_layers_over_weak_root(original_base, asc_smtype_list));
-        };
-        format!("if let {DATA}::{}::{}(_o) = &{base}.{DATA_VARIANT_FIELD} {{ {} }} else {{ Err({}::SemError::Contravariant) }}",
-            DATA_VARIANT_PREFIX.to_owned() + &inherited.name(),
-            DATA_PREFIX.to_owned() + &smtype.name(),
-            self.match_contravariant(asc_smtype_list, smtype_index + 1, "_o", original_base, sem_path),
-            sem_path.to_string())
-    }
+use crate::*;
+
+pub struct ProcessingStep3_6();
+
+impl ProcessingStep3_6 {
+    pub fn exec(&self, host: &mut SemHost, node: &Rc<SmType>, smtype: &Symbol, base_accessor: &proc_macro2::TokenStream, sem_path: &proc_macro2::TokenStream) {
+        let smtype_name_debug = format!("{}()", smtype.name());
+        let smtype_name = node.name.clone();
+        let attributes = node.attributes.clone();
+        let visi = node.visibility.clone();
+        let generics = smtype.generics();
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        let smtype_type = smtype.self_type();
+
+        // Define the structure M, as in
+        //
+        // ```
+        // struct M(Weak<dyn #DATA::#DATA_SEGMENT_PREFIX M>);
+        // ```
+        //
+        // (holding an `Index` under `type Handle = Index;` and a `std::sync::Weak`
+        // under `type Handle = Sync;`),
+        //
+        // or as in:
+        //
+        // ```
+        // struct M(InheritedM);
+        // ```
+        //
+        // if there is an inherited base. A generic data type inheriting
+        // a base additionally holds its type parameters.
+        if let Some(inherited_path) = smtype.inherits_path() {
+            let type_params = generics.type_params().map(|p| p.ident.clone()).collect::<Vec<_>>();
+            let phantom = if type_params.is_empty() {
+                proc_macro2::TokenStream::new()
+            } else {
+                quote! { , ::std::marker::PhantomData<fn() -> (#(#type_params,)*)> }
+            };
+            host.output.extend::<TokenStream>(quote! {
+                #(#attributes)*
+                #visi struct #smtype_name #generics (#inherited_path #phantom) #where_clause;
+
+                impl #impl_generics ::std::ops::Deref for #smtype_type #where_clause {
+                    type Target = #inherited_path;
+                    fn deref(&self) -> &Self::Target {
+                        &self.0
+                    }
+                }
+
+                impl #impl_generics PartialEq for #smtype_type #where_clause {
+                    fn eq(&self, other: &Self) -> bool {
+                        self.0 == other.0
+                    }
+                }
+
+                impl #impl_generics ::std::hash::Hash for #smtype_type #where_clause {
+                    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                        self.0.hash(state)
+                    }
+                }
+            }.try_into().unwrap());
+        } else {
+            let segment_trait = segment_trait(smtype, &host.data_module);
+            let (handle, eq, hash) = match host.handle_kind {
+                HandleKind::Weak => (
+                    quote! { ::std::rc::Weak<dyn #segment_trait> },
+                    quote! { self.0.ptr_eq(&other.0) },
+                    quote! { (self.0.as_ptr() as *const ()).hash(state) },
+                ),
+                HandleKind::Sync => (
+                    quote! { ::std::sync::Weak<dyn #segment_trait> },
+                    quote! { self.0.ptr_eq(&other.0) },
+                    quote! { (self.0.as_ptr() as *const ()).hash(state) },
+                ),
+                HandleKind::Index => (
+                    quote! { #sem_path::Index<dyn #segment_trait> },
+                    quote! { self.0 == other.0 },
+                    quote! { self.0.hash(state) },
+                ),
+            };
+            host.output.extend::<TokenStream>(quote! {
+                #(#attributes)*
+                #visi struct #smtype_name #generics (#handle) #where_clause;
+
+                impl #impl_generics PartialEq for #smtype_type #where_clause {
+                    fn eq(&self, other: &Self) -> bool {
+                        #eq
+                    }
+                }
+
+                impl #impl_generics ::std::hash::Hash for #smtype_type #where_clause {
+                    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                        #hash
+                    }
+                }
+            }.try_into().unwrap());
+
+            // Implement FromObject, converting an object of the arena holding
+            // the data of a data type of the segment to M.
+            let data_id = Ident::new(&host.data_module, Span::call_site());
+            let (object, from_object, is_object, object_key) = if host.handle_kind == HandleKind::Index {
+                (
+                    quote! { #sem_path::Index<dyn ::std::any::Any> },
+                    quote! {
+                        let handle = object.cast::<dyn #segment_trait>();
+                        handle.upgrade().map(|_| Self(handle))
+                    },
+                    quote! { self.0.cast() == *object },
+                    quote! { self.0.index() as usize },
+                )
+            } else {
+                let rc_path = host.rc_path();
+                let any_bounds = host.any_bounds();
+                let mut downcasts = proc_macro2::TokenStream::new();
+                for m in smtype.segment_smtype_list() {
+                    let m_data_id = Ident::new(&format!("{DATA_PREFIX}{}", m.name()), Span::call_site());
+                    let m_generics = m.generics();
+                    let (_, m_ty_generics, _) = m_generics.split_for_impl();
+                    downcasts.extend(quote! {
+                        if let Ok(data) = object.clone().downcast::<#data_id::#m_data_id #m_ty_generics>() {
+                            let data: #rc_path<dyn #segment_trait> = data;
+                            return Some(Self(#rc_path::downgrade(&data)));
+                        }
+                    });
+                }
+                (
+                    quote! { #rc_path<dyn #any_bounds> },
+                    quote! {
+                        #downcasts
+                        None
+                    },
+                    quote! { self.0.as_ptr() as *const () == #rc_path::as_ptr(object) as *const () },
+                    quote! { self.0.as_ptr() as *const () as usize },
+                )
+            };
+            host.output.extend::<TokenStream>(quote! {
+                impl #impl_generics #sem_path::FromObject<#object> for #smtype_type #where_clause {
+                    fn from_object(object: &#object) -> Option<Self> {
+                        #from_object
+                    }
+                    fn is_object(&self, object: &#object) -> bool {
+                        #is_object
+                    }
+                    fn object_key(&self) -> usize {
+                        #object_key
+                    }
+                }
+            }.try_into().unwrap());
+        }
+
+        // Implement Clone, Eq and Debug
+        let clone = smtype.layer(&quote! { self.0.clone() });
+        host.output.extend::<TokenStream>(quote! {
+            impl #impl_generics Clone for #smtype_type #where_clause {
+                fn clone(&self) -> Self {
+                    #clone
+                }
+            }
+
+            impl #impl_generics Eq for #smtype_type #where_clause {}
+
+            impl #impl_generics ::std::fmt::Debug for #smtype_type #where_clause {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    write!(f, #smtype_name_debug)
+                }
+            }
+        }.try_into().unwrap());
+
+        // Implement Trace, marking the data of the entity as reachable.
+        let runtime_path = host.runtime_path();
+        let trace = if smtype.inherits_path().is_some() {
+            quote! { #runtime_path::Trace::trace(&self.0, tracer) }
+        } else {
+            quote! {
+                if let Some(data) = self.0.upgrade() {
+                    tracer.mark(data);
+                }
+            }
+        };
+        host.output.extend::<TokenStream>(quote! {
+            impl #impl_generics #runtime_path::Trace for #smtype_type #where_clause {
+                fn trace(&self, tracer: &mut #runtime_path::Tracer) {
+                    #trace
+                }
+            }
+        }.try_into().unwrap());
+
+        // Index handles are Copy.
+        if host.handle_kind == HandleKind::Index {
+            host.output.extend::<TokenStream>(quote! {
+                impl #impl_generics Copy for #smtype_type #where_clause {}
+            }.try_into().unwrap());
+        }
+
+        // Output From<M> for InheritedM implementation (covariant conversion)
+        let mut base = quote! { v.0 };
+        for (_, inherited_path) in smtype.base_types() {
+            host.output.extend::<TokenStream>(quote! {
+                impl #impl_generics From<#smtype_type> for #inherited_path #where_clause {
+                    fn from(v: #smtype_type) -> Self {
+                        #base.clone()
+                    }
+                }
+            }.try_into().unwrap());
+            base = quote! { #base.0 };
+        }
+
+        // Output a TryFrom<M> for SubtypeM implementation (contravariant conversion)
+        // The base data accessor is taken from `v` rather than from `self`.
+        let mut v_base_accessor = quote! { v };
+        v_base_accessor.extend(base_accessor.clone().into_iter().skip(1));
+        for sm in smtype.segment_subtypes().iter() {
+            self.contravariance(host, &v_base_accessor, smtype, sm, sem_path);
+        }
+
+        // If the segment of M has a base, output a TryFrom<BaseM> for M implementation
+        // for the segment base and for each of its local bases.
+        let first = smtype.segment_asc_smtype_list()[0].clone();
+        let first_name = first.name_id();
+        let segment_base = smtype.segment_base();
+        let segment_base_path = segment_base.as_ref().map(|segment_base| {
+            smtype.base_types().into_iter().find(|(m, _)| m == segment_base).unwrap().1
+        });
+        if let Some(segment_base) = &segment_base {
+            let segment_base_path = segment_base_path.as_ref().unwrap();
+            let segment_base_index = smtype.base_types().iter().position(|(m, _)| m == segment_base).unwrap();
+            let name = smtype.name();
+            for (m, inherited_path) in smtype.base_types().into_iter().skip(segment_base_index) {
+                let m_name = m.name();
+                let try_from = if m != *segment_base {
+                    quote! {
+                        <#segment_base_path>::try_from(v).and_then(Self::try_from)
+                            .map_err(|e| e.with_source(#m_name).with_target(#name))
+                    }
+                } else if first == *smtype {
+                    let extension_data_method = Ident::new(EXTENSION_DATA_METHOD, Span::call_site());
+                    let type_name_method = Ident::new(TYPE_NAME_METHOD, Span::call_site());
+                    let segment_trait = segment_trait(smtype, &host.data_module);
+                    let layer = smtype.layer(&quote! { v });
+                    let rc_path = host.rc_path();
+                    // The data of a generic data type fails to downcast
+                    // if its generic arguments differ.
+                    let downcast = if smtype.is_generic() {
+                        quote! {
+                            else if v.#type_name_method() == #name {
+                                Err(#sem_path::SemError::Downcast { to: ::std::any::type_name::<Self>() })
+                            }
+                        }
+                    } else {
+                        proc_macro2::TokenStream::new()
+                    };
+                    quote! {
+                        if !v.is_alive() {
+                            Err(#sem_path::SemError::Dropped { data_type: #m_name })
+                        } else if v.#extension_data_method::<#rc_path<dyn #segment_trait>>().is_some() {
+                            Ok(#layer)
+                        } #downcast else {
+                            Err(#sem_path::SemError::Contravariant { from: #m_name, to: #name, actual: v.#type_name_method() })
+                        }
+                    }
+                } else {
+                    quote! {
+                        #first_name::try_from(v).and_then(#smtype_name::try_from)
+                            .map_err(|e| e.with_target(#name))
+                    }
+                };
+                host.output.extend::<TokenStream>(quote! {
+                    impl #impl_generics TryFrom<#inherited_path> for #smtype_type #where_clause {
+                        type Error = #sem_path::SemError;
+                        fn try_from(v: #inherited_path) -> Result<Self, Self::Error> {
+                            #try_from
+                        }
+                    }
+                }.try_into().unwrap());
+            }
+        }
+
+        // Implement DataType, relating M to the basemost data type
+        // of the hierarchy.
+        let name = smtype.name();
+        let data_type_impl = if let Some(segment_base_path) = &segment_base_path {
+            quote! {
+                type Root = <#segment_base_path as #sem_path::DataType>::Root;
+                fn into_root(self) -> Self::Root {
+                    <#segment_base_path as #sem_path::DataType>::into_root(<#segment_base_path>::from(self))
+                }
+                fn from_root(root: Self::Root) -> Result<Self, #sem_path::SemError> {
+                    <#segment_base_path as #sem_path::DataType>::from_root(root).and_then(Self::try_from)
+                        .map_err(|e| e.with_target(#name))
+                }
+            }
+        } else if first == *smtype {
+            quote! {
+                type Root = Self;
+                fn into_root(self) -> Self::Root {
+                    self
+                }
+                fn from_root(root: Self::Root) -> Result<Self, #sem_path::SemError> {
+                    Ok(root)
+                }
+            }
+        } else {
+            quote! {
+                type Root = #first_name;
+                fn into_root(self) -> Self::Root {
+                    #first_name::from(self)
+                }
+                fn from_root(root: Self::Root) -> Result<Self, #sem_path::SemError> {
+                    #smtype_name::try_from(root)
+                }
+            }
+        };
+        // The type identifiers of M and of its bases, continuing
+        // with those of an external base, if any.
+        let mut type_ids = vec![quote! { ::std::any::TypeId::of::<Self>() }];
+        let mut external_type_ids = proc_macro2::TokenStream::new();
+        for (m, inherited_path) in smtype.base_types() {
+            if m.is_external() {
+                external_type_ids = quote! {
+                    type_ids.extend(<#inherited_path as #sem_path::DataType>::type_ids());
+                };
+            } else {
+                type_ids.push(quote! { ::std::any::TypeId::of::<#inherited_path>() });
+            }
+        }
+        host.output.extend::<TokenStream>(quote! {
+            impl #impl_generics #sem_path::DataType for #smtype_type #where_clause {
+                #data_type_impl
+                fn type_ids() -> ::std::vec::Vec<::std::any::TypeId> {
+                    #[allow(unused_mut)]
+                    let mut type_ids = ::std::vec![#(#type_ids),*];
+                    #external_type_ids
+                    type_ids
+                }
+            }
+        }.try_into().unwrap());
+    }
+
+    fn contravariance(&self, host: &mut SemHost, base_accessor: &proc_macro2::TokenStream, base_smtype: &Symbol, subtype: &Symbol, sem_path: &proc_macro2::TokenStream) {
+        let base_smtype_name = base_smtype.name_id();
+        let subtype_name = subtype.name_id();
+        let first = &base_smtype.segment_asc_smtype_list()[0];
+        let try_data = try_data_accessor(host, base_smtype, base_accessor);
+        let data = data_of(first, &quote! { __data });
+        let data_tag_field = Ident::new(DATA_TAG_FIELD, Span::call_site());
+        let layers = Symbol::create_layers_over_weak_root(base_accessor, &subtype.segment_asc_smtype_list());
+        let type_name_method = Ident::new(TYPE_NAME_METHOD, Span::call_site());
+        let (from, to) = (base_smtype.name(), subtype.name());
+
+        // The instance is of the subtype if its type tag identifies
+        // the subtype or one of its subtypes in the segment.
+        let mut tags: Vec<usize> = vec![];
+        for m in subtype.segment_smtype_list() {
+            if !m.is_abstract() {
+                tags.push(m.segment_tag(false));
+            }
+            if m.is_extensible() {
+                tags.push(m.segment_tag(true));
+            }
+        }
+
+        // An abstract sealed subtype with no concrete subtypes has no
+        // instances, so the conversion always fails.
+        let is_subtype = if tags.is_empty() {
+            quote! { { let _ = &#data; false } }
+        } else {
+            quote! { matches!(#data.#data_tag_field, #(#tags)|*) }
+        };
+
+        host.output.extend::<TokenStream>(quote! {
+            impl TryFrom<#base_smtype_name> for #subtype_name {
+                type Error = #sem_path::SemError;
+                fn try_from(v: #base_smtype_name) -> Result<Self, Self::Error> {
+                    let __data = #try_data?;
+                    if #is_subtype {
+                        Ok(#layers)
+                    } else {
+                        Err(#sem_path::SemError::Contravariant { from: #from, to: #to, actual: v.#type_name_method() })
+                    }
+                }
+            }
+        }.try_into().unwrap());
+
+        for sm1 in subtype.segment_subtypes().iter() {
+            self.contravariance(host, base_accessor, base_smtype, sm1, sem_path);
+        }
+    }
 }
\ No newline at end of file
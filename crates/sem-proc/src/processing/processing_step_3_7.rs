@@ -1,117 +1,229 @@
-use crate::*;
-
-const CTOR_INIT_NAME: &'static str = "__ctor";
-
-pub struct ProcessingStep3_7();
-
-impl ProcessingStep3_7 {
-    // Define the constructor
-    pub fn exec(&self, _host: &mut SemHost, node: Option<&SmTypeConstructor>, smtype: &Symbol, asc_smtype_list: &[Symbol], arena_type_name: &str) {
-        let input = node.map(|node| node.inputs.clone()).unwrap_or(Punctuated::new());
-        let type_params = node.map(|node| [node.generics.lt_token.to_token_stream(), node.generics.params.to_token_stream(), node.generics.gt_token.to_token_stream()]).unwrap_or([
-            proc_macro2::TokenStream::new(),
-            proc_macro2::TokenStream::new(),
-            proc_macro2::TokenStream::new(),
-        ]);
-        let where_clause = node.map(|node| node.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new())).unwrap_or(proc_macro2::TokenStream::new());
-        let attr = node.map(|node| node.attributes.clone()).unwrap_or(vec![]);
-        let vis = node.map(|node| node.visibility.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new());
-
-        let ctor_init_name_id = Ident::new(CTOR_INIT_NAME, node.map(|node| node.name.span()).unwrap_or(Span::call_site()));
-        let arena_type_name_id = Ident::new(arena_type_name, Span::call_site());
-
-        // If the type inherits another type:
-        //
-        // * At the constructor code, invoke `InheritedM::#ctor_init_name_id(&self.0, ...super_arguments)`,
-        //   passing all `super(...)` arguments.
-        let mut super_code = proc_macro2::TokenStream::new();
-        if let Some(inherited_m) = smtype.inherits() {
-            let inherited_m_name = Ident::new(&inherited_m.name(), Span::call_site());
-            let super_arguments = node.map(|node| node.super_arguments.clone()).unwrap_or(Punctuated::new());
-            super_code.extend::<proc_macro2::TokenStream>(quote! {
-                #inherited_m_name::#ctor_init_name_id(&self.0, #super_arguments);
-            }.try_into().unwrap());
-        }
-
-        // Define the the instance `#ctor_init_name_id` method,
-        // containing super invokation code and structure initialization.
-        let statements = node.map(|node| node.statements.clone()).unwrap_or(vec![]);
-        smtype.method_output().borrow_mut().extend(quote! {
-            #(#attr)*
-            fn #ctor_init_name_id #(#type_params)*(&self, #input) #where_clause {
-                #super_code
-                #(#statements)*
-            }
-        });
-
-        // `M::new` output
-        let mut m_new_out = TokenStream::new();
-
-        // At `M::new`, let `__cto1` be a complex `M2(M1(__arena.allocate(#DATA::M1 { ... })))`
-        // (notice the data type layers) allocation initializing all data type variants's fields
-        // with their default values.
-        let initlayer1 = self.init_data(asc_smtype_list, 0);
-        let initlayer2 = proc_macro2::TokenStream::from_str(&Symbol::create_layers_over_weak_root(&format!("arena.allocate({})", initlayer1.to_string()), asc_smtype_list)).unwrap();
-        m_new_out.extend::<TokenStream>(quote! {
-            let __cto1 = #initlayer2;
-        }.try_into().unwrap());
-
-        // * Output a `__cto1.#ctor_init_name_id(...arguments);` call to `M::new`.
-        // * Output a `__cto1` return to `M::new`.
-        let input_args = convert_function_input_to_arguments(&input);
-        m_new_out.extend::<TokenStream>(quote! {
-            __cto1.#ctor_init_name_id(#input_args);
-            __cto1
-        }.try_into().unwrap());
-
-        // Output the constructor as a static `new` method (`M::new`) with
-        // a prepended `arena: &#arena_type_name_id` parameter.
-
-        let m_new_out: proc_macro2::TokenStream = m_new_out.into();
-
-        smtype.method_output().borrow_mut().extend(quote! {
-            #(#attr)*
-            #vis fn new #(#type_params)*(arena: &#arena_type_name_id, #input) -> Self #where_clause {
-                #m_new_out
-            }
-        });
-    }
-
-    fn init_data(&self, asc_smtype_list: &[Symbol], smtype_index: usize) -> proc_macro2::TokenStream {
-        let smtype = &asc_smtype_list[smtype_index];
-        let smtype_name = smtype.name();
-        let mut fields = proc_macro2::TokenStream::new();
-        for (name, field) in smtype.fields().borrow().iter() {
-            let name_id = Ident::new(name, Span::call_site());
-            let fv = field.field_init();
-            if field.is_ref() {
-                fields.extend(quote! {
-                    #name_id: ::std::cell::RefCell::new(#fv),
-                });
-            } else {
-                fields.extend(quote! {
-                    #name_id: ::std::cell::Cell::new(#fv),
-                });
-            }
-        }
-        let data_variant_no_subtype = proc_macro2::TokenStream::from_str(DATA_VARIANT_NO_SUBTYPE).unwrap();
-        let subtype_enum = proc_macro2::TokenStream::from_str(&format!("{DATA}::{DATA_VARIANT_PREFIX}{smtype_name}")).unwrap();
-        let variant = if smtype_index + 1 < asc_smtype_list.len() {
-            let next_m = asc_smtype_list[smtype_index + 1].name();
-            let next_m = Ident::new(&(DATA_PREFIX.to_owned() + &next_m), Span::call_site());
-            let i = self.init_data(asc_smtype_list, smtype_index + 1);
-            quote! { #subtype_enum::#next_m(::std::rc::Rc::new(#i)) }
-        } else {
-            quote! { #subtype_enum::#data_variant_no_subtype }
-        };
-        let data_variant_field = Ident::new(DATA_VARIANT_FIELD, Span::call_site());
-        let data_id = Ident::new(DATA, Span::call_site());
-        let smtype_data_name = Ident::new(&format!("{DATA_PREFIX}{}", smtype_name), Span::call_site());
-        quote! {
-            #data_id::#smtype_data_name {
-                #fields
-                #data_variant_field: #variant
-            }
-        }
-    }
+use crate::*;
+
+const CTOR_INIT_NAME: &'static str = "__ctor";
+
+pub struct ProcessingStep3_7();
+
+impl ProcessingStep3_7 {
+    // Define the constructor
+    pub fn exec(&self, host: &mut SemHost, smtype_node: &Rc<SmType>, smtype: &Symbol, asc_smtype_list: &[Symbol], arena_type: &proc_macro2::TokenStream) {
+        let node = smtype_node.constructor.as_ref();
+        let smtype_vis = smtype_node.visibility.clone();
+        let input = node.map(|node| node.inputs.clone()).unwrap_or(Punctuated::new());
+        let type_params = node.map(|node| [node.generics.lt_token.to_token_stream(), node.generics.params.to_token_stream(), node.generics.gt_token.to_token_stream()]).unwrap_or([
+            proc_macro2::TokenStream::new(),
+            proc_macro2::TokenStream::new(),
+            proc_macro2::TokenStream::new(),
+        ]);
+        let where_clause = node.map(|node| node.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new())).unwrap_or(proc_macro2::TokenStream::new());
+        let attr = node.map(|node| node.attributes.clone()).unwrap_or(vec![]);
+        let vis = node.map(|node| node.visibility.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new());
+
+        let ctor_init_name_id = Ident::new(CTOR_INIT_NAME, node.map(|node| node.name.span()).unwrap_or(Span::call_site()));
+
+        // If the type inherits another type:
+        //
+        // * At the constructor code, invoke `InheritedM::#ctor_init_name_id(&self.0, ...super_arguments)`,
+        //   passing all `super(...)` arguments.
+        let mut super_code = proc_macro2::TokenStream::new();
+        if let Some(inherited_path) = smtype.inherits_path() {
+            let super_arguments = node.map(|node| node.super_arguments.clone()).unwrap_or(Punctuated::new());
+            super_code.extend::<proc_macro2::TokenStream>(quote! {
+                <#inherited_path>::#ctor_init_name_id(&self.0, #super_arguments);
+            }.try_into().unwrap());
+        }
+
+        // Define the the instance `#ctor_init_name_id` method,
+        // containing super invokation code and structure initialization.
+        //
+        // It is visible as the data type, so that subtypes defined
+        // by other `sem!` invocations may invoke it.
+        let statements = node.map(|node| node.statements.clone()).unwrap_or(vec![]);
+        smtype.method_output().borrow_mut().extend(quote! {
+            #(#attr)*
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            #smtype_vis fn #ctor_init_name_id #(#type_params)*(&self, #input) #where_clause {
+                #super_code
+                #(#statements)*
+            }
+        });
+
+        // Define the hidden `M::#EXTENSION_NEW_METHOD` method, which allocates M
+        // holding the data of a subtype defined by another `sem!` invocation
+        // or of a generic subtype, unless M is not extensible.
+        //
+        // A sealed data type instead implements no `Extensible` trait, which
+        // `sem_extend!` requires from the data types it extends.
+        if smtype.is_extensible() {
+            self.define_extension_new(host, &smtype_vis, smtype, asc_smtype_list, arena_type);
+        }
+        if !smtype.is_sealed() {
+            let sem_path = &host.sem_path;
+            let smtype_type = smtype.self_type();
+            let generics = smtype.generics();
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+            host.output.extend::<TokenStream>(quote! {
+                impl #impl_generics #sem_path::Extensible for #smtype_type #where_clause {}
+            }.try_into().unwrap());
+        }
+
+        // An abstract data type is not instantiated directly,
+        // thus it has no `M::new` method.
+        if smtype.is_abstract() {
+            return;
+        }
+
+        // `M::new` output
+        let mut m_new_out = TokenStream::new();
+
+        // At `M::new`, let `__cto1` be a complex `M2(M1(__arena.allocate(#DATA::M2 { ... })))`
+        // (notice the data type layers) allocation initializing the fields of M and
+        // of its bases in the segment with their default values.
+        //
+        // If the segment of M has a base, the data is instead
+        // allocated through `BaseM::#EXTENSION_NEW_METHOD`.
+        let initlayer1 = self.init_data(host, asc_smtype_list, asc_smtype_list.len() - 1, false);
+        let initlayer2 = self.allocate(host, &initlayer1, asc_smtype_list, &quote! { Self });
+        m_new_out.extend::<TokenStream>(quote! {
+            let __cto1 = #initlayer2;
+        }.try_into().unwrap());
+
+        // * Output a `__cto1.#ctor_init_name_id(...arguments);` call to `M::new`.
+        // * Output a `__cto1` return to `M::new`.
+        let (new_input, input_args) = bind_inputs_by_position(host, &input);
+        m_new_out.extend::<TokenStream>(quote! {
+            __cto1.#ctor_init_name_id(#input_args);
+            __cto1
+        }.try_into().unwrap());
+
+        // Output the constructor as a static `new` method (`M::new`) with
+        // a prepended `arena: &#arena_type` parameter.
+
+        let m_new_out: proc_macro2::TokenStream = m_new_out.into();
+
+        // `M::new` spans the constructor, or the data type name if there is none.
+        let new_id = Ident::new("new", node.map(|node| node.name.span()).unwrap_or(smtype_node.name.span()));
+
+        smtype.method_output().borrow_mut().extend(quote! {
+            #(#attr)*
+            #vis fn #new_id #(#type_params)*(arena: &#arena_type, #new_input) -> Self #where_clause {
+                #m_new_out
+            }
+        });
+    }
+
+    /// Defines the hidden `M::#EXTENSION_NEW_METHOD` method.
+    fn define_extension_new(&self, host: &SemHost, smtype_vis: &Visibility, smtype: &Symbol, asc_smtype_list: &[Symbol], arena_type: &proc_macro2::TokenStream) {
+        let extension_new_method = Ident::new(EXTENSION_NEW_METHOD, Span::call_site());
+        let vtable_name = Ident::new(&format!("{VTABLE_PREFIX}{}", smtype.name()), Span::call_site());
+        let generics = smtype.generics();
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let runtime_path = host.runtime_path();
+        let initlayer1 = self.init_data(host, asc_smtype_list, asc_smtype_list.len() - 1, true);
+        let initlayer2 = self.allocate(host, &initlayer1, asc_smtype_list, &quote! { __D });
+        let sem_path = &host.sem_path;
+        smtype.method_output().borrow_mut().extend(quote! {
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            #smtype_vis fn #extension_new_method<__D: #sem_path::DataType>(arena: &#arena_type, ext: #runtime_path::Extension<dyn #vtable_name #ty_generics>) -> Self {
+                #initlayer2
+            }
+        });
+    }
+
+    /// Returns `M2(M1(...))` layers over the allocation of the data `init`
+    /// of an entity of the data type `data_type`.
+    ///
+    /// If the segment has a base, the data is held as extension data of the base,
+    /// along with the vtable of the first data type of the segment.
+    fn allocate(&self, host: &SemHost, init: &proc_macro2::TokenStream, asc_smtype_list: &[Symbol], data_type: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let first = &asc_smtype_list[0];
+        let base = if let Some(segment_base_path) = first.inherits_path() {
+            let generics = first.generics();
+            let (_, ty_generics, _) = generics.split_for_impl();
+            let turbofish = ty_generics.as_turbofish();
+            let data_id = Ident::new(&host.data_module, Span::call_site());
+            let vtable = Ident::new(&format!("{EXTENSION_VTABLE_PREFIX}{}", first.name()), Span::call_site());
+            let extension_new_method = Ident::new(EXTENSION_NEW_METHOD, Span::call_site());
+            let segment_trait = segment_trait(first, &host.data_module);
+            let rc_path = host.rc_path();
+            let runtime_path = host.runtime_path();
+            quote! {
+                <#segment_base_path>::#extension_new_method::<#data_type>(arena, #runtime_path::Extension {
+                    vtable: &#data_id::#vtable #turbofish(::std::marker::PhantomData),
+                    data: ::std::boxed::Box::new(#rc_path::new(#init) as #rc_path<dyn #segment_trait>),
+                })
+            }
+        } else if host.handle_kind == HandleKind::Index {
+            let segment_trait = segment_trait(first, &host.data_module);
+            quote! { arena.allocate::<#data_type, _>(::std::rc::Rc::new(#init) as ::std::rc::Rc<dyn #segment_trait>) }
+        } else {
+            quote! { arena.allocate::<#data_type, _>(#init) }
+        };
+        Symbol::create_layers_over_weak_root(&base, asc_smtype_list)
+    }
+
+    /// Initializes the data of `asc_smtype_list[smtype_index]`, holding the data of
+    /// the data types of the segment from the inherited base upwards. If `extension`
+    /// is true, the data of the first data type holds the `ext` data of a subtype of
+    /// the last data type, defined by another `sem!` invocation or generic.
+    fn init_data(&self, host: &SemHost, asc_smtype_list: &[Symbol], smtype_index: usize, extension: bool) -> proc_macro2::TokenStream {
+        let smtype = &asc_smtype_list[smtype_index];
+        let smtype_name = smtype.name();
+        let mut fields = proc_macro2::TokenStream::new();
+        for (name, field) in smtype.fields().borrow().iter() {
+            let name_id = Ident::new(name, Span::call_site());
+            let fv = field.field_init();
+            let cfg = cfg_attributes(&field.field_attributes());
+            let cell_path = host.cell_path(field.is_ref());
+            fields.extend(quote! {
+                #(#cfg)*
+                #name_id: #cell_path::new(#fv),
+            });
+        }
+
+        // The data of the first data type holds the type tag of the last
+        // data type and the extension data, if the segment may hold any.
+        if smtype_index == 0 {
+            let tag = asc_smtype_list.last().unwrap().segment_tag(extension);
+            let data_tag_field = Ident::new(DATA_TAG_FIELD, Span::call_site());
+            let data_extension_field = Ident::new(DATA_EXTENSION_FIELD, Span::call_site());
+            fields.extend(quote! {
+                #data_tag_field: #tag,
+            });
+            if smtype.segment_holds_extension() {
+                let ext = if extension {
+                    quote! { ::std::option::Option::Some(::std::boxed::Box::new(ext)) }
+                } else {
+                    quote! { ::std::option::Option::None }
+                };
+                fields.extend(quote! {
+                    #data_extension_field: #ext,
+                });
+            }
+        } else {
+            let data_base_field = Ident::new(DATA_BASE_FIELD, Span::call_site());
+            let base = self.init_data(host, asc_smtype_list, smtype_index - 1, extension);
+            fields.extend(quote! {
+                #data_base_field: #base,
+            });
+        }
+        if smtype.generics().type_params().next().is_some() {
+            let data_phantom_field = Ident::new(DATA_PHANTOM_FIELD, Span::call_site());
+            fields.extend(quote! {
+                #data_phantom_field: ::std::marker::PhantomData,
+            });
+        }
+        let data_id = Ident::new(&host.data_module, Span::call_site());
+        let smtype_data_name = Ident::new(&format!("{DATA_PREFIX}{}", smtype_name), Span::call_site());
+        let generics = smtype.generics();
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let turbofish = ty_generics.as_turbofish();
+        quote! {
+            #data_id::#smtype_data_name #turbofish {
+                #fields
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,322 +1,424 @@
-use syn::Meta;
-use crate::*;
-
-pub const NONDISPATCH_PREFIX: &'static str = "__nd_";
-
-pub struct ProcessingStep3_8();
-
-impl ProcessingStep3_8 {
-    // Process a method
-    pub fn exec(&self, host: &mut SemHost, node: &Rc<SmTypeMethod>, smtype: &Symbol) -> bool {
-        let input = &node.inputs;
-        let type_params = [node.generics.lt_token.to_token_stream(), node.generics.params.to_token_stream(), node.generics.gt_token.to_token_stream()];
-        let where_clause = node.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new());
-        let vis = node.visibility.clone();
-        let name = node.name.clone();
-        let mut result_annotation = proc_macro2::TokenStream::new();
-        if let Some(t) = &node.result_type {
-            result_annotation.extend::<proc_macro2::TokenStream>(quote!{->});
-            result_annotation.extend::<proc_macro2::TokenStream>(t.to_token_stream());
-        }
-
-        // Static method
-        if Self::begins_with_no_receiver(&node.inputs) {
-            let attr = node.attributes.borrow().clone();
-            let stmt = &node.statements;
-            smtype.method_output().borrow_mut().extend(quote! {
-                #(#attr)*
-                #vis fn #name #(#type_params)*(#input) #result_annotation #where_clause {
-                    #stmt
-                }
-            });
-            return true;
-        }
-
-        // Validate receiver
-        if !Self::begins_with_instance_receiver(&node.inputs) {
-            node.inputs.span().unwrap().error("Instance receiver must be exactly `&self`.").emit();
-            return false;
-        }
-
-        // Remove the receiver
-        let mut inputs1 = node.inputs.iter().cloned().collect::<Vec<_>>();
-        inputs1.remove(0);
-        let mut inputs = Punctuated::<FnArg, Comma>::new();
-        inputs.extend(inputs1);
-
-        // * Look for the #[doc] attribute.
-        // * Look for the #[inheritdoc] attribute.
-        let mut doc_attr: Vec<syn::Attribute> = vec![];
-        let mut inheritdoc_index: Option<usize> = None;
-        let mut i = 0usize;
-        for attr in node.attributes.borrow().iter() {
-            if let Meta::List(list) = &attr.meta {
-                if list.path.to_token_stream().to_string() == "doc" {
-                    doc_attr.push(attr.clone());
-                }
-            } else if let Meta::NameValue(name_value) = &attr.meta {
-                if name_value.path.to_token_stream().to_string() == "doc" {
-                    doc_attr.push(attr.clone());
-                }
-            } else if let Meta::Path(p) = &attr.meta {
-                if p.to_token_stream().to_string() == "inheritdoc" {
-                    inheritdoc_index = Some(i);
-                }
-            }
-            i += 1;
-        }
-
-        // Create a `MethodSlot` with the appropriate settings.
-        let slot = host.factory.create_method_slot(name.to_string(), smtype.clone(), doc_attr);
-
-        // Map node to slot
-        host.semantics.set(&node, Some(slot.clone()));
-
-        // Contribute the method slot to the data type.
-        if smtype.methods().get(&slot.name()).is_some() {
-            name.span().unwrap().error(format!("Redefining '{}'.", slot.name())).emit();
-            return false;
-        }
-        smtype.methods().set(slot.name(), slot.clone());
-
-        // Check if the method has a `#[inheritdoc]` attribute; if it has one:
-        //
-        // * Remove it
-        // * Lookup method in one of the base data types
-        // * Inherit RustDoc comment
-        if let Some(i) = inheritdoc_index {
-            node.attributes.borrow_mut().remove(i);
-
-            if let Some(base_method) = smtype.lookup_method_in_base_smtype(&slot.name()) {
-                slot.set_doc_attribute(base_method.doc_attribute());
-                for attr in base_method.doc_attribute() {
-                    node.attributes.borrow_mut().push(attr);
-                }
-            } else {
-                name.span().unwrap().error(format!("No method '{}' in base.", slot.name())).emit();
-            }
-        }
-
-        // Define `nondispatch_name` as nondispatch prefix plus method name.
-        let nondispatch_name = format!("{NONDISPATCH_PREFIX}{}", slot.name());
-        let nondispatch_name_id = Ident::new(&nondispatch_name, name.span());
-
-        // Define input argument list
-        let input_args = convert_function_input_to_arguments(&inputs);
-
-        // Process super expressions
-        let statements = self.process_super_expression(node.statements.clone(), smtype, &slot);
-
-        // If the method is marked as "override"
-        //
-        // * Lookup for a method with the same name in one of the base data types
-        // * Contribute "overriding" return call code to the respective
-        //   override logic mapping according to smtype inheritance.
-        if node.is_override {
-            if let Some(base_method) = smtype.lookup_method_in_base_smtype(&slot.name()) {
-                self.perform_override(&slot.name(), base_method.override_logic_mapping(), &base_method.defined_in(), smtype, &input_args);
-            } else {
-                name.span().unwrap().error(format!("No method '{}' in base.", slot.name())).emit();
-            }
-        }
-
-        let mut attr = node.attributes.borrow().clone();
-
-        // Remove #[doc] attributes from nondispatch methods
-        // for less cost.
-        let mut indices = Vec::<usize>::new();
-        let mut i: usize = 0;
-        for attr in attr.iter() {
-            if let Meta::List(list) = &attr.meta {
-                if list.path.to_token_stream().to_string() == "doc" {
-                    indices.push(i);
-                }
-            } else if let Meta::NameValue(name_value) = &attr.meta {
-                if name_value.path.to_token_stream().to_string() == "doc" {
-                    indices.push(i);
-                }
-            }
-            i += 1;
-        }
-        for i in indices.iter().rev() {
-            attr.remove(*i);
-        }
-
-        smtype.method_output().borrow_mut().extend(quote! {
-            #(#attr)*
-            fn #nondispatch_name_id #(#type_params)*(&self, #inputs) #result_annotation #where_clause {
-                #statements
-            }
-        });
-        
-        true
-    }
-
-    fn begins_with_no_receiver(input: &Punctuated<FnArg, Comma>) -> bool {
-        if let Some(first) = input.first() {
-            !(matches!(first, FnArg::Receiver(_)))
-        } else {
-            true
-        }
-    }
-
-    // Checks whether method formally begins with the exact `&self` receiver.
-    fn begins_with_instance_receiver(input: &Punctuated<FnArg, Comma>) -> bool {
-        if let Some(first) = input.first() {
-            if let FnArg::Receiver(rec) = first {
-                if !rec.attrs.is_empty() || rec.mutability.is_some() {
-                    return false;
-                }
-                let Some(reference) = rec.reference.as_ref() else {
-                    return false
-                };
-                if reference.1.is_some() {
-                    return false;
-                }
-                // Ignore the type for now, assuming Self.
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
-    }
-
-    fn process_super_expression(&self, input: proc_macro2::TokenStream, smtype: &Symbol, method_slot: &Symbol) -> proc_macro2::TokenStream {
-        let mut input = input.into_iter();
-        let mut output = proc_macro2::TokenStream::new();
-        while let Some(token1) = input.next() {
-            match &token1 {
-                proc_macro2::TokenTree::Ident(id) => {
-                    if id.to_string() != "super" {
-                        output.extend([token1.clone()]);
-                        continue;
-                    }
-                    let Some(token2) = input.next() else {
-                        output.extend([token1.clone()]);
-                        continue;
-                    };
-                    let proc_macro2::TokenTree::Punct(p) = &token2 else {
-                        output.extend([token1.clone()]);
-                        output.extend([token2.clone()]);
-                        continue;
-                    };
-                    if p.to_string() != "." {
-                        output.extend([token1.clone()]);
-                        output.extend([token2.clone()]);
-                        continue;
-                    }
-                    let Some(token3) = input.next() else {
-                        output.extend([token1.clone()]);
-                        output.extend([token2.clone()]);
-                        continue;
-                    };
-                    let proc_macro2::TokenTree::Ident(id) = &token3 else {
-                        output.extend([token1.clone()]);
-                        output.extend([token2.clone()]);
-                        output.extend([token3.clone()]);
-                        continue;
-                    };
-                    let Some(token4) = input.next() else {
-                        output.extend([token1.clone()]);
-                        output.extend([token2.clone()]);
-                        output.extend([token3.clone()]);
-                        continue;
-                    };
-                    let proc_macro2::TokenTree::Group(g) = &token4 else {
-                        output.extend([token1.clone()]);
-                        output.extend([token2.clone()]);
-                        output.extend([token3.clone()]);
-                        output.extend([token4.clone()]);
-                        continue;
-                    };
-                    if g.delimiter() != proc_macro2::Delimiter::Parenthesis {
-                        output.extend([token1.clone()]);
-                        output.extend([token2.clone()]);
-                        output.extend([token3.clone()]);
-                        output.extend([token4.clone()]);
-                        continue;
-                    }
-
-                    // Found super expression.
-
-                    // Lookup for a method in one of the base data types.
-                    let Some(base_method) = smtype.lookup_method_in_base_smtype(&id.to_string()) else {
-                        id.span().unwrap().error(format!("No method '{}' in base.", id.to_string())).emit();
-                        continue;
-                    };
-
-                    // Let base be "self" followed by n = delta_of_descending_list_until_base_type
-                    // (where `base_type` is the base found method's `.defined_in()` call)
-                    // repeats of "".0".
-                    let mut base = "self".to_owned();
-                    let mut m = smtype.clone();
-                    while let Some(m1) = m.inherits() {
-                        base.push_str(".0");
-                        if m1 == base_method.defined_in() {
-                            break;
-                        }
-                        m = m1;
-                    }
-                    let base = proc_macro2::TokenStream::from_str(&base).unwrap();
-
-                    // Replace super.m(...) by BaseM::#nondispatch_name_id(&#base, ...)
-                    let nondispatch_name = format!("{NONDISPATCH_PREFIX}{}", base_method.name());
-                    let nondispatch_name_id = Ident::new(&nondispatch_name, Span::call_site());
-                    let base_smtype = Ident::new(&base_method.defined_in().name(), Span::call_site());
-                    let super_args = self.process_super_expression(g.stream(), smtype, method_slot);
-                    output.extend(quote! {
-                        #base_smtype::#nondispatch_name_id(&#base, #super_args)
-                    });
-                },
-                proc_macro2::TokenTree::Group(g) => {
-                    let stream = self.process_super_expression(g.stream(), smtype, method_slot);
-                    output.extend([proc_macro2::TokenTree::Group(proc_macro2::Group::new(g.delimiter(), stream))]);
-                },
-                _ => {
-                    output.extend([token1.clone()]);
-                },
-            }
-        }
-        output
-    }
-
-    fn perform_override(&self, method_name: &str, mut override_logic_mapping: SharedMap<Symbol, Rc<OverrideLogicMapping>>, base_smtype: &Symbol, target_smtype: &Symbol, input_args: &Punctuated<proc_macro2::TokenStream, Comma>) {
-        let smtype_list = target_smtype.asc_smtype_list();
-        let mut i = 0usize;
-        for m in smtype_list.iter() {
-            if m == base_smtype {
-                break;
-            }
-            i += 1;
-        }
-        for m in smtype_list[(i + 1)..(smtype_list.len() - 1)].iter() {
-            if let Some(old_mapping) = override_logic_mapping.get(m) {
-                override_logic_mapping = old_mapping.override_logic_mapping();
-            } else {
-                let new_mapping = Rc::new(OverrideLogicMapping::new());
-                override_logic_mapping.set(m.clone(), new_mapping.clone());
-                override_logic_mapping = new_mapping.override_logic_mapping();
-            }
-        }
-
-        // Generate layers
-        let mut layers = String::new();
-        let mut parens = 0usize;
-        for m in smtype_list[(i + 1)..].iter().rev() {
-            layers.push_str(&format!("{}(", m.name()));
-            parens += 1;
-        }
-        layers.push_str("self.clone()");
-        layers.push_str(&")".repeat(parens));
-
-        let new_mapping = Rc::new(OverrideLogicMapping::new());
-        let layers = proc_macro2::TokenStream::from_str(&layers).unwrap();
-        let method_name_id = Ident::new(&method_name, Span::call_site());
-        new_mapping.set_override_code(Some(quote! {
-            return #layers.#method_name_id(#input_args);
-        }));
-        override_logic_mapping.set(target_smtype.clone(), new_mapping.clone());
-    }
-}
\ No newline at end of file
+use syn::Meta;
+use crate::*;
+
+pub const NONDISPATCH_PREFIX: &'static str = "__nd_";
+
+pub struct ProcessingStep3_8();
+
+impl ProcessingStep3_8 {
+    // Process a method
+    pub fn exec(&self, host: &mut SemHost, node: &Rc<SmTypeMethod>, smtype: &Symbol) -> bool {
+        let input = &node.inputs;
+        let type_params = [node.generics.lt_token.to_token_stream(), node.generics.params.to_token_stream(), node.generics.gt_token.to_token_stream()];
+        let where_clause = node.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new());
+        let vis = node.visibility.clone();
+        let name = node.name.clone();
+        let mut result_annotation = proc_macro2::TokenStream::new();
+        if let Some(t) = &node.result_type {
+            result_annotation.extend::<proc_macro2::TokenStream>(quote!{->});
+            result_annotation.extend::<proc_macro2::TokenStream>(t.to_token_stream());
+        }
+
+        // An abstract method must be an instance method
+        // defined in an abstract data type.
+        if node.is_abstract {
+            if !Self::begins_with_instance_receiver(&node.inputs) {
+                host.error_spanned(&node.inputs, "Abstract method must have exactly the `&self` receiver.");
+                return false;
+            }
+            if !smtype.is_abstract() {
+                host.error(name.span(), format!("Abstract method '{}' must be defined in an abstract data type.", name));
+                return false;
+            }
+        }
+
+        // Static method
+        if Self::begins_with_no_receiver(&node.inputs) {
+            let attr = node.attributes.borrow().clone();
+            let stmt = &node.statements;
+            smtype.method_output().borrow_mut().extend(quote! {
+                #(#attr)*
+                #vis fn #name #(#type_params)*(#input) #result_annotation #where_clause {
+                    #stmt
+                }
+            });
+            return true;
+        }
+
+        // Validate receiver
+        if !Self::begins_with_instance_receiver(&node.inputs) {
+            host.error_spanned(&node.inputs, "Instance receiver must be exactly `&self`.");
+            return false;
+        }
+
+        // Remove the receiver
+        let mut inputs1 = node.inputs.iter().cloned().collect::<Vec<_>>();
+        inputs1.remove(0);
+        let mut inputs = Punctuated::<FnArg, Comma>::new();
+        inputs.extend(inputs1);
+
+        // * Look for the #[doc] attribute.
+        // * Look for the #[inheritdoc] attribute.
+        let mut doc_attr: Vec<syn::Attribute> = vec![];
+        let mut inheritdoc_index: Option<usize> = None;
+        let mut i = 0usize;
+        for attr in node.attributes.borrow().iter() {
+            if let Meta::List(list) = &attr.meta {
+                if list.path.to_token_stream().to_string() == "doc" {
+                    doc_attr.push(attr.clone());
+                }
+            } else if let Meta::NameValue(name_value) = &attr.meta {
+                if name_value.path.to_token_stream().to_string() == "doc" {
+                    doc_attr.push(attr.clone());
+                }
+            } else if let Meta::Path(p) = &attr.meta {
+                if p.to_token_stream().to_string() == "inheritdoc" {
+                    inheritdoc_index = Some(i);
+                }
+            }
+            i += 1;
+        }
+
+        // Create a `MethodSlot` with the appropriate settings.
+        let slot = host.factory.create_method_slot(name.to_string(), smtype.clone(), doc_attr, node.clone());
+
+        // Map node to slot
+        host.semantics.set(&node, Some(slot.clone()));
+
+        // Contribute the method slot to the data type.
+        if smtype.methods().get(&slot.name()).is_some() {
+            host.error(name.span(), format!("Redefining '{}'.", slot.name()));
+            return false;
+        }
+        smtype.methods().set(slot.name(), slot.clone());
+
+        // Check if the method has a `#[inheritdoc]` attribute; if it has one:
+        //
+        // * Remove it
+        // * Lookup method in one of the base data types
+        // * Inherit RustDoc comment
+        if let Some(i) = inheritdoc_index {
+            node.attributes.borrow_mut().remove(i);
+
+            if let Some(base_method) = smtype.lookup_method_in_base_smtype(&slot.name()) {
+                slot.set_doc_attribute(base_method.doc_attribute());
+                for attr in base_method.doc_attribute() {
+                    node.attributes.borrow_mut().push(attr);
+                }
+            } else {
+                host.error(name.span(), format!("No method '{}' in base.", slot.name()));
+            }
+        }
+
+        // Define `nondispatch_name` as nondispatch prefix plus method name.
+        let nondispatch_name = format!("{NONDISPATCH_PREFIX}{}", slot.name());
+        let nondispatch_name_id = Ident::new(&nondispatch_name, name.span());
+
+        // Process super expressions
+        let statements = self.process_super_expression(host, node.statements.clone(), smtype);
+
+        // If the method is marked as "override"
+        //
+        // * Lookup for a method with the same name in one of the base data types
+        // * If the method is defined in the data type's segment, it is dispatched
+        //   by the type tag of the instance (see `ProcessingStep4_1`).
+        //
+        // If the method is defined before the data type's segment, or if no base data type
+        // defines it but the hierarchy extends an external data type, the method is dispatched
+        // through the vtable traits. In that case, every segment root from the defining data type
+        // downwards, except the data type itself, implicitly overrides the method, which is then
+        // dispatched by the type tag within the segment of the data type.
+        if node.is_override {
+            let base_method = smtype.lookup_method_in_base_smtype(&slot.name());
+            if let Some(base_method) = &base_method {
+                if !self.check_override_signature(host, node, base_method) {
+                    return false;
+                }
+            }
+            let segment = smtype.segment_asc_smtype_list();
+            if base_method.as_ref().is_some_and(|m| segment.contains(&m.defined_in())) {
+                // Dispatched by the type tag.
+            } else if base_method.is_some() || smtype.extension_base().is_some() {
+                if !node.generics.params.is_empty() {
+                    host.error(name.span(), format!("Method '{}' must not be generic, since it overrides a method across a generic or external data type.", slot.name()));
+                    return false;
+                }
+                let local_asc_smtype_list = smtype.local_asc_smtype_list();
+                let start = base_method.as_ref().map(|m| local_asc_smtype_list.iter().position(|m1| *m1 == m.defined_in()).unwrap() + 1).unwrap_or(0);
+                for m in local_asc_smtype_list[start..(local_asc_smtype_list.len() - 1)].iter() {
+                    if m.is_segment_root() {
+                        let signature = base_method.as_ref().map(|base_method| signature_at(m, base_method)).unwrap_or(node.clone());
+                        self.define_implicit_override(host, &signature, m);
+                    }
+                }
+            } else {
+                host.error(name.span(), format!("No method '{}' in base.", slot.name()));
+            }
+        }
+
+        let mut attr = node.attributes.borrow().clone();
+
+        // Remove #[doc] attributes from nondispatch methods
+        // for less cost.
+        let mut indices = Vec::<usize>::new();
+        let mut i: usize = 0;
+        for attr in attr.iter() {
+            if let Meta::List(list) = &attr.meta {
+                if list.path.to_token_stream().to_string() == "doc" {
+                    indices.push(i);
+                }
+            } else if let Meta::NameValue(name_value) = &attr.meta {
+                if name_value.path.to_token_stream().to_string() == "doc" {
+                    indices.push(i);
+                }
+            }
+            i += 1;
+        }
+        for i in indices.iter().rev() {
+            attr.remove(*i);
+        }
+
+        // The nondispatch method of an abstract method is never reached,
+        // since every non-abstract subtype overrides it.
+        if node.is_abstract {
+            let message = format!("Abstract method '{}::{}' called.", smtype.name(), slot.name());
+            smtype.method_output().borrow_mut().extend(quote! {
+                #(#attr)*
+                #[doc(hidden)]
+                #[allow(unused_variables)]
+                #vis fn #nondispatch_name_id #(#type_params)*(&self, #inputs) #result_annotation #where_clause {
+                    unreachable!(#message)
+                }
+            });
+            return true;
+        }
+
+        // The nondispatch method is visible as the method, so that subtypes
+        // defined by other `sem!` invocations may invoke it.
+        smtype.method_output().borrow_mut().extend(quote! {
+            #(#attr)*
+            #[doc(hidden)]
+            #vis fn #nondispatch_name_id #(#type_params)*(&self, #inputs) #result_annotation #where_clause {
+                #statements
+            }
+        });
+        
+        true
+    }
+
+    /// Checks that the signature of an override matches the one of the overriden
+    /// method in what syntax decides, reporting each mismatch at the override: the
+    /// number of parameters and the number of generic parameters of each kind. The
+    /// types, which may be spelled differently, such as through another path or an
+    /// alias, are checked by the compiler through the dispatch code.
+    fn check_override_signature(&self, host: &mut SemHost, node: &SmTypeMethod, base_method: &Symbol) -> bool {
+        let base = base_method.signature();
+        let base_name = base_method.defined_in().name();
+        let name = &node.name;
+        let errors = host.errors.len();
+
+        let arity = |generics: &Generics| (generics.lifetimes().count(), generics.type_params().count(), generics.const_params().count());
+        let ((lifetimes, types, consts), (base_lifetimes, base_types, base_consts)) = (arity(&node.generics), arity(&base.generics));
+        if (lifetimes, types, consts) != (base_lifetimes, base_types, base_consts) {
+            host.error(name.span(), format!("'{name}' has {lifetimes} lifetime, {types} type and {consts} const parameter(s), but the overriden method of '{base_name}' has {base_lifetimes}, {base_types} and {base_consts}."));
+        }
+
+        // Compare the parameters after the receiver.
+        let (count, base_count) = (node.inputs.len() - 1, base.inputs.len() - 1);
+        if count != base_count {
+            host.error_spanned(&node.inputs, format!("'{name}' has {count} parameter(s), but the overriden method of '{base_name}' has {base_count}."));
+        }
+
+        host.errors.len() == errors
+    }
+
+    fn begins_with_no_receiver(input: &Punctuated<FnArg, Comma>) -> bool {
+        if let Some(first) = input.first() {
+            !(matches!(first, FnArg::Receiver(_)))
+        } else {
+            true
+        }
+    }
+
+    // Checks whether method formally begins with the exact `&self` receiver.
+    fn begins_with_instance_receiver(input: &Punctuated<FnArg, Comma>) -> bool {
+        if let Some(first) = input.first() {
+            if let FnArg::Receiver(rec) = first {
+                if !rec.attrs.is_empty() || rec.mutability.is_some() {
+                    return false;
+                }
+                let Some(reference) = rec.reference.as_ref() else {
+                    return false
+                };
+                if reference.1.is_some() {
+                    return false;
+                }
+                // Ignore the type for now, assuming Self.
+                true
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+
+    fn process_super_expression(&self, host: &mut SemHost, input: proc_macro2::TokenStream, smtype: &Symbol) -> proc_macro2::TokenStream {
+        let mut input = input.into_iter();
+        let mut output = proc_macro2::TokenStream::new();
+        while let Some(token1) = input.next() {
+            match &token1 {
+                proc_macro2::TokenTree::Ident(id) => {
+                    if id.to_string() != "super" {
+                        output.extend([token1.clone()]);
+                        continue;
+                    }
+                    let Some(token2) = input.next() else {
+                        output.extend([token1.clone()]);
+                        continue;
+                    };
+                    let proc_macro2::TokenTree::Punct(p) = &token2 else {
+                        output.extend([token1.clone()]);
+                        output.extend([token2.clone()]);
+                        continue;
+                    };
+                    if p.to_string() != "." {
+                        output.extend([token1.clone()]);
+                        output.extend([token2.clone()]);
+                        continue;
+                    }
+                    let Some(token3) = input.next() else {
+                        output.extend([token1.clone()]);
+                        output.extend([token2.clone()]);
+                        continue;
+                    };
+                    let proc_macro2::TokenTree::Ident(id) = &token3 else {
+                        output.extend([token1.clone()]);
+                        output.extend([token2.clone()]);
+                        output.extend([token3.clone()]);
+                        continue;
+                    };
+                    let Some(token4) = input.next() else {
+                        output.extend([token1.clone()]);
+                        output.extend([token2.clone()]);
+                        output.extend([token3.clone()]);
+                        continue;
+                    };
+                    let proc_macro2::TokenTree::Group(g) = &token4 else {
+                        output.extend([token1.clone()]);
+                        output.extend([token2.clone()]);
+                        output.extend([token3.clone()]);
+                        output.extend([token4.clone()]);
+                        continue;
+                    };
+                    if g.delimiter() != proc_macro2::Delimiter::Parenthesis {
+                        output.extend([token1.clone()]);
+                        output.extend([token2.clone()]);
+                        output.extend([token3.clone()]);
+                        output.extend([token4.clone()]);
+                        continue;
+                    }
+
+                    // Found super expression.
+
+                    // Lookup for a method in one of the base data types.
+                    //
+                    // If no base data type defines the method, but the hierarchy extends
+                    // an external data type, replace super.m(...) by #base.#nondispatch_name_id(...),
+                    // where #base is the external base value.
+                    let Some(base_method) = smtype.lookup_method_in_base_smtype(&id.to_string()) else {
+                        if smtype.extension_base().is_some() {
+                            let mut base = quote_spanned! { token1.span() => self };
+                            for _ in smtype.local_asc_smtype_list() {
+                                base = quote! { #base.0 };
+                            }
+                            let nondispatch_name_id = Ident::new(&format!("{NONDISPATCH_PREFIX}{}", id), id.span());
+                            let super_args = self.process_super_expression(host, g.stream(), smtype);
+                            let super_args = quote_spanned! { g.span() => (#super_args) };
+                            output.extend(quote! {
+                                #base.#nondispatch_name_id #super_args
+                            });
+                            continue;
+                        }
+                        host.error(id.span(), format!("No method '{}' in base.", id));
+                        continue;
+                    };
+                    if base_method.is_abstract() {
+                        host.error(id.span(), format!("Cannot call abstract method '{}' through `super`.", id));
+                        continue;
+                    }
+
+                    // Let base be `self` followed by n = delta_of_descending_list_until_base_type
+                    // (where `base_type` is the base found method's `.defined_in()` call)
+                    // repeats of `.0`.
+                    let mut base = quote! { self };
+                    let mut m = smtype.clone();
+                    while let Some(m1) = m.inherits() {
+                        base = quote! { #base.0 };
+                        if m1 == base_method.defined_in() {
+                            break;
+                        }
+                        m = m1;
+                    }
+
+                    // Replace super.m(...) by BaseM::#nondispatch_name_id(&#base, ...),
+                    // spanning the super expression.
+                    let nondispatch_name = format!("{NONDISPATCH_PREFIX}{}", base_method.name());
+                    let nondispatch_name_id = Ident::new(&nondispatch_name, id.span());
+                    let base_smtype = Ident::new(&base_method.defined_in().name(), token1.span());
+                    let super_args = self.process_super_expression(host, g.stream(), smtype);
+                    let super_args = quote_spanned! { g.span() => (&#base, #super_args) };
+                    output.extend(quote! {
+                        #base_smtype::#nondispatch_name_id #super_args
+                    });
+                },
+                proc_macro2::TokenTree::Group(g) => {
+                    let stream = self.process_super_expression(host, g.stream(), smtype);
+                    let mut g1 = proc_macro2::Group::new(g.delimiter(), stream);
+                    g1.set_span(g.span());
+                    output.extend([proc_macro2::TokenTree::Group(g1)]);
+                },
+                _ => {
+                    output.extend([token1.clone()]);
+                },
+            }
+        }
+        output
+    }
+
+    /// Defines an implicit override of a method at `first`, a segment root,
+    /// given the signature of the method as seen from `first`.
+    fn define_implicit_override(&self, host: &mut SemHost, node: &Rc<SmTypeMethod>, first: &Symbol) {
+        let implicit_node = Rc::new(SmTypeMethod {
+            attributes: RefCell::new(vec![]),
+            visibility: node.visibility.clone(),
+            is_override: true,
+            is_abstract: false,
+            name: node.name.clone(),
+            generics: node.generics.clone(),
+            inputs: node.inputs.clone(),
+            result_type: node.result_type.clone(),
+            statements: proc_macro2::TokenStream::new(),
+        });
+        let slot = host.factory.create_method_slot(node.name.to_string(), first.clone(), vec![], implicit_node.clone());
+        host.semantics.set(&implicit_node, Some(slot.clone()));
+        first.methods().set(slot.name(), slot.clone());
+        host.implicit_overrides.entry(first.clone()).or_default().push(implicit_node);
+
+        // The nondispatch method invokes the one of the inherited base.
+        let mut inputs = Punctuated::<FnArg, Comma>::new();
+        inputs.extend(node.inputs.iter().skip(1).cloned());
+        let type_params = [node.generics.lt_token.to_token_stream(), node.generics.params.to_token_stream(), node.generics.gt_token.to_token_stream()];
+        let where_clause = node.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or_default();
+        let vis = node.visibility.clone();
+        let result_annotation = node.result_type.as_ref().map(|t| quote! { -> #t });
+        let nondispatch_name_id = Ident::new(&format!("{NONDISPATCH_PREFIX}{}", slot.name()), Span::call_site());
+        let (inputs, input_args) = bind_inputs_by_position(host, &inputs);
+        first.method_output().borrow_mut().extend(quote! {
+            #[doc(hidden)]
+            #vis fn #nondispatch_name_id #(#type_params)*(&self, #inputs) #result_annotation #where_clause {
+                self.0.#nondispatch_name_id(#input_args)
+            }
+        });
+    }
+}
@@ -1,76 +1,232 @@
-use crate::*;
-
-pub struct ProcessingStep4_1();
-
-impl ProcessingStep4_1 {
-    // Process a method
-    pub fn exec(&self, host: &mut SemHost, node: &Rc<SmTypeMethod>, smtype: &Symbol) {
-        // Skip if it is not mapped to an instance method slot.
-        let Some(slot) = host.semantics.get(node) else {
-            return;
-        };
-
-        let attr = node.attributes.borrow().clone();
-        let type_params = [node.generics.lt_token.to_token_stream(), node.generics.params.to_token_stream(), node.generics.gt_token.to_token_stream()];
-        let where_clause = node.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new());
-        let vis = node.visibility.clone();
-        let name = node.name.clone();
-        let mut result_annotation = proc_macro2::TokenStream::new();
-        if let Some(t) = &node.result_type {
-            result_annotation.extend::<proc_macro2::TokenStream>(quote!{->});
-            result_annotation.extend::<proc_macro2::TokenStream>(t.to_token_stream());
-        }
-
-        // Remove the receiver
-        let mut inputs1 = node.inputs.iter().cloned().collect::<Vec<_>>();
-        inputs1.remove(0);
-        let mut inputs = Punctuated::<FnArg, Comma>::new();
-        inputs.extend(inputs1);
-
-        // Define `nondispatch_name` as nondispatch prefix plus method name.
-        let nondispatch_name = format!("{NONDISPATCH_PREFIX}{}", slot.name());
-        let nondispatch_name_id = Ident::new(&nondispatch_name, Span::call_site());
-
-        // Define input argument list
-        let input_args = convert_function_input_to_arguments(&inputs);
-
-        // Contribute the method #method_name with prepended dynamic dispatch logic,
-        // invoking `self.#nondispatch_name(#input_args)` at the end of the method body,
-        // to the `impl` output.
-        let dynamic_dispatch = self.generate_dynamic_dispatch(slot.override_logic_mapping());
-
-        smtype.method_output().borrow_mut().extend(quote! {
-            #(#attr)*
-            #vis fn #name #(#type_params)*(&self, #inputs) #result_annotation #where_clause {
-                #dynamic_dispatch
-                self.#nondispatch_name_id(#input_args)
-            }
-        });
-    }
-
-    fn generate_dynamic_dispatch(&self, mapping: SharedMap<Symbol, Rc<OverrideLogicMapping>>) -> proc_macro2::TokenStream {
-        let mut out = proc_macro2::TokenStream::new();
-        let mut first = true;
-        for (subtype, logic) in mapping.borrow().iter() {
-            let subtype_name = Ident::new(&subtype.name(), Span::call_site());
-            if !first {
-                out.extend(quote! { else });
-            }
-            let mut d1 = self.generate_dynamic_dispatch(logic.override_logic_mapping());
-            if d1.is_empty() {
-                if let Some(code) = logic.override_code() {
-                    d1.extend(code);
-                }
-            } else if let Some(code) = logic.override_code() {
-                d1.extend(quote! { else { #code } });
-            }
-            out.extend(quote! {
-                if self.is::<#subtype_name>() {
-                    #d1
-                }
-            });
-            first = false;
-        }
-        out
-    }
+use crate::*;
+
+pub struct ProcessingStep4_1();
+
+impl ProcessingStep4_1 {
+    // Process a method
+    pub fn exec(&self, host: &mut SemHost, node: &Rc<SmTypeMethod>, smtype: &Symbol) {
+        // Skip if it is not mapped to an instance method slot.
+        let Some(slot) = host.semantics.get(node) else {
+            return;
+        };
+
+        let attr = node.attributes.borrow().clone();
+        let type_params = [node.generics.lt_token.to_token_stream(), node.generics.params.to_token_stream(), node.generics.gt_token.to_token_stream()];
+        let where_clause = node.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or(proc_macro2::TokenStream::new());
+        let vis = node.visibility.clone();
+        let name = node.name.clone();
+        let mut result_annotation = proc_macro2::TokenStream::new();
+        if let Some(t) = &node.result_type {
+            result_annotation.extend::<proc_macro2::TokenStream>(quote!{->});
+            result_annotation.extend::<proc_macro2::TokenStream>(t.to_token_stream());
+        }
+
+        // Remove the receiver
+        let mut inputs1 = node.inputs.iter().cloned().collect::<Vec<_>>();
+        inputs1.remove(0);
+        let mut inputs = Punctuated::<FnArg, Comma>::new();
+        inputs.extend(inputs1);
+
+        // Define `nondispatch_name` as nondispatch prefix plus method name.
+        let nondispatch_name = format!("{NONDISPATCH_PREFIX}{}", slot.name());
+        let nondispatch_name_id = Ident::new(&nondispatch_name, name.span());
+
+        // Define input argument list, forwarding the parameters by position,
+        // as the patterns binding them belong to the nondispatch method.
+        let (inputs, input_args) = bind_inputs_by_position(host, &inputs);
+
+        // Contribute the method #method_name with prepended dynamic dispatch logic,
+        // invoking `self.#nondispatch_name(#input_args)` at the end of the method body,
+        // to the `impl` output.
+        let dynamic_dispatch = self.generate_dynamic_dispatch(host, smtype, &slot, &input_args);
+
+        smtype.method_output().borrow_mut().extend(quote! {
+            #(#attr)*
+            #vis fn #name #(#type_params)*(&self, #inputs) #result_annotation #where_clause {
+                #dynamic_dispatch
+                self.#nondispatch_name_id(#input_args)
+            }
+        });
+    }
+
+    /// Generates code dispatching a method by the type tag of the instance, which
+    /// identifies the most specific data type of the instance within the segment of
+    /// `smtype` and whether it holds the extension data of a subtype.
+    ///
+    /// * An instance of a data type of the segment dispatches to the nondispatch
+    ///   method of its nearest override, if any.
+    /// * Unless the method is generic, an instance holding extension data dispatches
+    ///   through the vtable trait of the data type the extension data inherits.
+    fn generate_dynamic_dispatch(&self, host: &SemHost, smtype: &Symbol, slot: &Symbol, input_args: &Punctuated<proc_macro2::TokenStream, Comma>) -> proc_macro2::TokenStream {
+        let name = slot.signature().name.clone();
+        let is_generic = !slot.signature().generics.params.is_empty();
+        let nondispatch_name_id = Ident::new(&format!("{NONDISPATCH_PREFIX}{}", slot.name()), Span::call_site());
+        let data_tag_field = Ident::new(DATA_TAG_FIELD, Span::call_site());
+
+        // Group the tags of the subtypes by their override.
+        let mut overrides: Vec<(Symbol, Vec<usize>)> = vec![];
+        let mut arms = proc_macro2::TokenStream::new();
+        for subtype in smtype.segment_smtype_list() {
+            if !subtype.is_abstract() {
+                if let Some(target) = self.nearest_override(smtype, &subtype, &slot.name()) {
+                    let tag = subtype.segment_tag(false);
+                    match overrides.iter_mut().find(|(m, _)| *m == target) {
+                        Some((_, tags)) => tags.push(tag),
+                        None => overrides.push((target, vec![tag])),
+                    }
+                }
+            }
+            if !is_generic && subtype.is_extensible() {
+                let tag = subtype.segment_tag(true);
+                let this = if subtype == *smtype {
+                    quote! { self }
+                } else {
+                    let layers = self.layers(smtype, &subtype);
+                    quote! { &#layers }
+                };
+                let vtable_name = Ident::new(&format!("{VTABLE_PREFIX}{}", subtype.name()), Span::call_site());
+                let ext = self.extension_of(host, &subtype);
+                let result = quote_spanned! { self.result_span(&subtype, slot) => __result };
+                arms.extend(quote! {
+                    #tag => {
+                        let __ext = #ext;
+                        let __result = #vtable_name::#name(__ext.vtable, #this, #input_args);
+                        return #result;
+                    },
+                });
+            }
+        }
+        for (target, tags) in overrides {
+            let layers = self.layers(smtype, &target);
+            let result = quote_spanned! { self.result_span(&target, slot) => __result };
+            arms.extend(quote! {
+                #(#tags)|* => {
+                    let __result = #layers.#nondispatch_name_id(#input_args);
+                    return #result;
+                },
+            });
+        }
+        if arms.is_empty() {
+            return arms;
+        }
+
+        let first = &smtype.segment_asc_smtype_list()[0];
+        let data = data_accessor(host, smtype, &base_accessor(smtype));
+        let data_of_first = data_of(first, &quote! { __data });
+        quote! {
+            let __data = #data;
+            let __data = #data_of_first;
+            match __data.#data_tag_field {
+                #arms
+                _ => {},
+            }
+        }
+    }
+
+    /// Returns the span of the return type of the method `slot` as seen from
+    /// `subtype`, at which a result of another type than the one of `slot`
+    /// is reported, or the span of the method name if it returns `()`.
+    fn result_span(&self, subtype: &Symbol, slot: &Symbol) -> Span {
+        let signature = subtype.lookup_method(&slot.name()).unwrap().signature();
+        signature.result_type.as_ref().map(|t| t.span()).unwrap_or(signature.name.span())
+    }
+
+    /// Returns the extension data held by `__data`, the data of the first data type
+    /// of a segment, along with the vtable of `subtype`, for an instance tagged as
+    /// holding extension data that inherits `subtype`.
+    fn extension_of(&self, host: &SemHost, subtype: &Symbol) -> proc_macro2::TokenStream {
+        let data_extension_field = Ident::new(DATA_EXTENSION_FIELD, Span::call_site());
+        let vtable_name = Ident::new(&format!("{VTABLE_PREFIX}{}", subtype.name()), Span::call_site());
+        let generics = subtype.generics();
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let runtime_path = host.runtime_path();
+        let message = format!("The extension data of a '{}' instance is missing or of another data type.", subtype.name());
+        quote! {
+            __data.#data_extension_field.as_ref()
+                .and_then(|ext| ext.downcast_ref::<#runtime_path::Extension<dyn #vtable_name #ty_generics>>())
+                .expect(#message)
+        }
+    }
+
+    /// Contributes the hidden `#TYPE_NAME_METHOD` method, returning the name of the
+    /// most specific data type of the instance. It is dispatched by the type tag of
+    /// the instance, and through the vtable trait for extension data.
+    pub fn define_type_name(&self, host: &SemHost, vis: &Visibility, smtype: &Symbol) {
+        let type_name_method = Ident::new(TYPE_NAME_METHOD, Span::call_site());
+        let data_tag_field = Ident::new(DATA_TAG_FIELD, Span::call_site());
+        let mut arms = proc_macro2::TokenStream::new();
+        for subtype in smtype.segment_smtype_list() {
+            if !subtype.is_abstract() {
+                let tag = subtype.segment_tag(false);
+                let name = subtype.name();
+                arms.extend(quote! {
+                    #tag => #name,
+                });
+            }
+            if !subtype.is_extensible() {
+                continue;
+            }
+            let tag = subtype.segment_tag(true);
+            let this = if subtype == *smtype {
+                quote! { self }
+            } else {
+                let layers = self.layers(smtype, &subtype);
+                quote! { &#layers }
+            };
+            let vtable_name = Ident::new(&format!("{VTABLE_PREFIX}{}", subtype.name()), Span::call_site());
+            let ext = self.extension_of(host, &subtype);
+            arms.extend(quote! {
+                #tag => {
+                    let __ext = #ext;
+                    #vtable_name::#type_name_method(__ext.vtable, #this)
+                },
+            });
+        }
+
+        let first = &smtype.segment_asc_smtype_list()[0];
+        let data = data_accessor(host, smtype, &base_accessor(smtype));
+        let data_of_first = data_of(first, &quote! { __data });
+        smtype.method_output().borrow_mut().extend(quote! {
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            #vis fn #type_name_method(&self) -> &'static str {
+                let __data = #data;
+                let __data = #data_of_first;
+                match __data.#data_tag_field {
+                    #arms
+                    _ => unreachable!(),
+                }
+            }
+        });
+    }
+
+    /// Returns the data type defining the nearest override of a method of `smtype`
+    /// for an instance of `subtype`, if it is not `smtype` itself.
+    fn nearest_override(&self, smtype: &Symbol, subtype: &Symbol, method_name: &str) -> Option<Symbol> {
+        let list = subtype.asc_smtype_list();
+        let i = list.iter().position(|m| m == smtype).unwrap();
+        let mut target: Option<Symbol> = None;
+        for m in list[(i + 1)..].iter() {
+            let Some(method) = m.methods().get(&method_name.to_owned()) else {
+                continue;
+            };
+            if !method.signature().is_override {
+                break;
+            }
+            target = Some(m.clone());
+        }
+        target
+    }
+
+    /// Returns `MN(...(M1(self.clone())))` layers converting `self`, a `smtype`
+    /// value, to a value of `subtype`, one of its subtypes in the same segment.
+    fn layers(&self, smtype: &Symbol, subtype: &Symbol) -> proc_macro2::TokenStream {
+        let list = subtype.asc_smtype_list();
+        let i = list.iter().position(|m| m == smtype).unwrap();
+        let mut layers = quote! { self.clone() };
+        for m in list[(i + 1)..].iter() {
+            layers = m.layer(&layers);
+        }
+        layers
+    }
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+use crate::*;
+
+pub struct ProcessingStep4_2();
+
+impl ProcessingStep4_2 {
+    /// Defines the vtable trait of a data type, through which methods are dispatched
+    /// to the extension data of subtypes, and, if the data type's data is held as
+    /// extension data of its inherited base, implements the vtable trait of the base.
+    pub fn exec(&self, host: &mut SemHost, smtype_node: &Rc<SmType>, smtype: &Symbol, base_accessor: &proc_macro2::TokenStream) {
+        let vis = smtype_node.visibility.clone();
+        let vtable_name = Ident::new(&format!("{VTABLE_PREFIX}{}", smtype.name()), Span::call_site());
+        let generics = smtype.generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let smtype_type = smtype.self_type();
+
+        // Define the vtable trait. Each method defaults to the nearest
+        // nondispatch method, unless it is abstract.
+        let mut vtable_methods = proc_macro2::TokenStream::new();
+        for method in self.visible_methods(smtype) {
+            let signature = signature_at(smtype, &method);
+            let (name_id, inputs, input_args, result_annotation, where_clause) = self.split_signature(host, &signature);
+            let nondispatch_name_id = Ident::new(&format!("{NONDISPATCH_PREFIX}{}", method.name()), Span::call_site());
+            if method.is_abstract() {
+                vtable_methods.extend(quote! {
+                    fn #name_id(&self, __this: &#smtype_type, #inputs) #result_annotation #where_clause;
+                });
+            } else {
+                vtable_methods.extend(quote! {
+                    fn #name_id(&self, __this: &#smtype_type, #inputs) #result_annotation #where_clause {
+                        __this.#nondispatch_name_id(#input_args)
+                    }
+                });
+            }
+        }
+        let any_bounds = host.any_bounds();
+        // The vtable trait also returns the name of the most specific data type
+        // and traces the extension data.
+        let type_name_method = Ident::new(TYPE_NAME_METHOD, Span::call_site());
+        let trace_method = Ident::new(TRACE_METHOD, Span::call_site());
+        let runtime_path = host.runtime_path();
+        vtable_methods.extend(quote! {
+            fn #type_name_method(&self, __this: &#smtype_type) -> &'static str;
+            fn #trace_method(&self, __data: &dyn ::std::any::Any, tracer: &mut #runtime_path::Tracer);
+        });
+        host.output.extend::<TokenStream>(quote! {
+            #[doc(hidden)]
+            #[allow(non_camel_case_types, private_interfaces)]
+            #vis trait #vtable_name #generics: #any_bounds #where_clause {
+                #vtable_methods
+            }
+        }.try_into().unwrap());
+
+        // Contribute the hidden `#EXTENSION_DATA_METHOD` method, returning the extension
+        // data of a subtype, if any and unless the entity has been dropped, downcasted
+        // to a concrete type.
+        if smtype.segment_holds_extension() {
+            self.define_extension_data(host, &vis, smtype, base_accessor);
+        }
+
+        // If the data type's data is held as extension data of its inherited base, define
+        // the zero-sized `#DATA::#EXTENSION_VTABLE_PREFIX M` type and implement the vtable
+        // trait of the base for it, dispatching the overriden methods.
+        let Some(segment_base) = smtype.inherits().filter(|_| smtype.is_segment_root()) else {
+            return;
+        };
+        let data_id = Ident::new(&host.data_module, Span::call_site());
+        let inherited_path = smtype.inherits_path().unwrap();
+        let vtable_path = smtype.inherits_sibling_path(VTABLE_PREFIX).unwrap();
+        let layer = smtype.layer(&quote! { __this.clone() });
+        let segment_trait = segment_trait(smtype, &host.data_module);
+        let rc_path = host.rc_path();
+        let mut overrides = quote! {
+            fn #type_name_method(&self, __this: &#inherited_path) -> &'static str {
+                #layer.#type_name_method()
+            }
+            fn #trace_method(&self, __data: &dyn ::std::any::Any, tracer: &mut #runtime_path::Tracer) {
+                if let Some(data) = __data.downcast_ref::<#rc_path<dyn #segment_trait>>() {
+                    #runtime_path::Trace::trace(&**data, tracer);
+                }
+            }
+        };
+        let mut names = smtype.methods().borrow().keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        for name in names.iter() {
+            let signature = smtype.methods().get(name).unwrap().signature();
+            if !signature.is_override || !signature.generics.params.is_empty() {
+                continue;
+            }
+            let (name_id, inputs, input_args, result_annotation, where_clause) = self.split_signature(host, &signature);
+            overrides.extend(quote! {
+                fn #name_id(&self, __this: &#inherited_path, #inputs) #result_annotation #where_clause {
+                    #layer.#name_id(#input_args)
+                }
+            });
+        }
+
+        // The abstract methods of a local inherited base that the data type does not
+        // override are never reached, since the data type is abstract.
+        if !segment_base.is_external() {
+            for method in self.visible_methods(&segment_base) {
+                if !method.is_abstract() || names.contains(&method.name()) {
+                    continue;
+                }
+                let signature = signature_at(smtype, &method);
+                let (name_id, inputs, _, result_annotation, where_clause) = self.split_signature(host, &signature);
+                let message = format!("Abstract method '{}::{}' called.", method.defined_in().name(), method.name());
+                overrides.extend(quote! {
+                    #[allow(unused_variables)]
+                    fn #name_id(&self, __this: &#inherited_path, #inputs) #result_annotation #where_clause {
+                        unreachable!(#message)
+                    }
+                });
+            }
+        }
+
+        let extension_vtable_id = Ident::new(&format!("{EXTENSION_VTABLE_PREFIX}{}", smtype.name()), Span::call_site());
+        let type_params = generics.type_params().map(|p| p.ident.clone()).collect::<Vec<_>>();
+        host.data_output.extend(quote! {
+            pub struct #extension_vtable_id #generics (pub ::std::marker::PhantomData<fn() -> (#(#type_params,)*)>) #where_clause;
+        });
+
+        // An external base must not be sealed.
+        if segment_base.is_external() {
+            let sem_path = &host.sem_path;
+            let assert_extensible = quote_spanned! { inherited_path.span() =>
+                #sem_path::assert_extensible::<#inherited_path>();
+            };
+            host.output.extend::<TokenStream>(quote! {
+                impl #impl_generics #data_id::#extension_vtable_id #ty_generics #where_clause {
+                    #[allow(dead_code)]
+                    fn __sem_assert_extensible() {
+                        #assert_extensible
+                    }
+                }
+            }.try_into().unwrap());
+        }
+        host.output.extend::<TokenStream>(quote! {
+            impl #impl_generics #vtable_path for #data_id::#extension_vtable_id #ty_generics #where_clause {
+                #overrides
+            }
+        }.try_into().unwrap());
+    }
+
+    /// Returns the non-generic instance methods visible at a data type, defined by
+    /// the same `sem!` invocation, starting from the basemost data type.
+    fn visible_methods(&self, smtype: &Symbol) -> Vec<Symbol> {
+        let mut method_names: Vec<String> = vec![];
+        for m in smtype.local_asc_smtype_list().iter() {
+            let mut names = m.methods().borrow().keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                if !method_names.contains(&name) {
+                    method_names.push(name);
+                }
+            }
+        }
+        method_names.iter()
+            .map(|name| smtype.lookup_method(name).unwrap())
+            .filter(|method| method.signature().generics.params.is_empty())
+            .collect()
+    }
+
+    /// Defines the hidden `#EXTENSION_DATA_METHOD` method of a data type.
+    fn define_extension_data(&self, host: &SemHost, vis: &Visibility, smtype: &Symbol, base_accessor: &proc_macro2::TokenStream) {
+        let vtable_name = Ident::new(&format!("{VTABLE_PREFIX}{}", smtype.name()), Span::call_site());
+        let generics = smtype.generics();
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let runtime_path = host.runtime_path();
+        let extension_data_method = Ident::new(EXTENSION_DATA_METHOD, Span::call_site());
+        let first = &smtype.segment_asc_smtype_list()[0];
+        let try_data = try_data_accessor(host, smtype, base_accessor);
+        let data = data_of(first, &quote! { __data });
+        let data_extension_field = Ident::new(DATA_EXTENSION_FIELD, Span::call_site());
+        smtype.method_output().borrow_mut().extend(quote! {
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            #vis fn #extension_data_method<__T: Clone + 'static>(&self) -> Option<__T> {
+                let __data = #try_data.ok()?;
+                #data.#data_extension_field.as_ref()
+                    .and_then(|ext| ext.downcast_ref::<#runtime_path::Extension<dyn #vtable_name #ty_generics>>())
+                    .and_then(|ext| ext.data.downcast_ref::<__T>())
+                    .cloned()
+            }
+        });
+    }
+
+    /// Splits a method signature into its name, inputs without receiver,
+    /// bound by position, input arguments, result annotation and where clause.
+    fn split_signature(&self, host: &mut SemHost, signature: &SmTypeMethod) -> (Ident, Punctuated<FnArg, Comma>, Punctuated<proc_macro2::TokenStream, Comma>, proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        let mut inputs = Punctuated::<FnArg, Comma>::new();
+        inputs.extend(signature.inputs.iter().skip(1).cloned());
+        let (inputs, input_args) = bind_inputs_by_position(host, &inputs);
+        let result_annotation = signature.result_type.as_ref().map(|t| quote! { -> #t }).unwrap_or_default();
+        let where_clause = signature.generics.where_clause.as_ref().map(|c| c.to_token_stream()).unwrap_or_default();
+        (signature.name.clone(), inputs, input_args, result_annotation, where_clause)
+    }
+}
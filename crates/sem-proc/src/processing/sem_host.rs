@@ -1,21 +1,93 @@
-use crate::*;
-
-pub struct SemHost {
-    pub factory: LmtFactory,
-    pub semantics: TreeSemantics<Symbol>,
-    pub smtype_slots: HashMap<String, Symbol>,
-    pub output: TokenStream,
-    pub data_output: proc_macro2::TokenStream,
-}
-
-impl SemHost {
-    pub fn new() -> Self {
-        Self {
-            factory: LmtFactory::new(),
-            semantics: TreeSemantics::new(),
-            smtype_slots: HashMap::new(),
-            output: TokenStream::new(),
-            data_output: proc_macro2::TokenStream::new(),
-        }
-    }
+use crate::*;
+
+pub struct SemHost {
+    pub factory: LmtFactory,
+    pub semantics: TreeSemantics<Symbol>,
+    pub smtype_slots: HashMap<String, Symbol>,
+    /// Data types defined by other `sem!` invocations, by path.
+    pub external_smtype_slots: HashMap<String, Symbol>,
+    /// Methods implicitly overriden by the basemost data type of a hierarchy
+    /// extending an external data type, since one of its subtypes overrides them.
+    pub implicit_overrides: HashMap<Symbol, Vec<Rc<SmTypeMethod>>>,
+    pub output: TokenStream,
+    pub data_output: proc_macro2::TokenStream,
+    /// Name of the data module, derived from the first basemost data type.
+    pub data_module: String,
+    /// Path to the `hydroperx_sem` crate.
+    pub sem_path: proc_macro2::TokenStream,
+    pub handle_kind: HandleKind,
+    /// Errors reported by the processing steps, output
+    /// as `compile_error!` invocations.
+    pub errors: Vec<syn::Error>,
+}
+
+impl SemHost {
+    pub fn new() -> Self {
+        Self {
+            factory: LmtFactory::new(),
+            semantics: TreeSemantics::new(),
+            smtype_slots: HashMap::new(),
+            external_smtype_slots: HashMap::new(),
+            implicit_overrides: HashMap::new(),
+            output: TokenStream::new(),
+            data_output: proc_macro2::TokenStream::new(),
+            data_module: DATA.to_owned(),
+            sem_path: proc_macro2::TokenStream::new(),
+            handle_kind: HandleKind::Weak,
+            errors: vec![],
+        }
+    }
+
+    /// Reports an error at a span.
+    pub fn error(&mut self, span: Span, message: impl std::fmt::Display) {
+        self.errors.push(syn::Error::new(span, message));
+    }
+
+    /// Reports an error spanning the tokens of a syntax node.
+    pub fn error_spanned(&mut self, tokens: impl ToTokens, message: impl std::fmt::Display) {
+        self.errors.push(syn::Error::new_spanned(tokens, message));
+    }
+
+    /// Returns the reported errors as `compile_error!` invocations.
+    pub fn error_output(&self) -> TokenStream {
+        self.errors.iter().map(syn::Error::to_compile_error).collect::<proc_macro2::TokenStream>().into()
+    }
+
+    /// Path to the module holding the runtime items of the handle kind,
+    /// such as `FieldRef`: the `hydroperx_sem::sync` module for `Sync` handles.
+    pub fn runtime_path(&self) -> proc_macro2::TokenStream {
+        let sem_path = &self.sem_path;
+        match self.handle_kind {
+            HandleKind::Sync => quote! { #sem_path::sync },
+            _ => sem_path.clone(),
+        }
+    }
+
+    /// Path to the reference-counted pointer holding the data.
+    pub fn rc_path(&self) -> proc_macro2::TokenStream {
+        match self.handle_kind {
+            HandleKind::Sync => quote! { ::std::sync::Arc },
+            _ => quote! { ::std::rc::Rc },
+        }
+    }
+
+    /// Path to the cell holding a field, which is a `RefCell`
+    /// for a `ref` field and a `Cell` otherwise.
+    pub fn cell_path(&self, is_ref: bool) -> proc_macro2::TokenStream {
+        let sem_path = &self.sem_path;
+        match (self.handle_kind, is_ref) {
+            (HandleKind::Sync, true) => quote! { #sem_path::sync::RefCell },
+            (HandleKind::Sync, false) => quote! { #sem_path::sync::Cell },
+            (_, true) => quote! { ::std::cell::RefCell },
+            (_, false) => quote! { ::std::cell::Cell },
+        }
+    }
+
+    /// Bounds of the type-erased extension data and of the vtable traits.
+    pub fn any_bounds(&self) -> proc_macro2::TokenStream {
+        match self.handle_kind {
+            HandleKind::Sync => quote! { ::std::any::Any + ::std::marker::Send + ::std::marker::Sync },
+            _ => quote! { ::std::any::Any },
+        }
+    }
 }
\ No newline at end of file
@@ -1,366 +1,623 @@
-use crate::*;
-
-pub struct Arena<T> {
-    data: RefCell<Vec<Rc<T>>>,
-}
-
-impl<T> Arena<T> {
-    pub fn new() -> Self {
-        Self {
-            data: RefCell::new(vec![]),
-        }
-    }
-
-    pub fn allocate(&self, value: T) -> Weak<T> {
-        let obj = Rc::new(value);
-        self.data.borrow_mut().push(obj.clone());
-        Rc::downgrade(&obj)
-    }
-}
-
-pub struct LmtFactory {
-    arena: Arena<Symbol1>,
-}
-
-impl LmtFactory {
-    pub fn new() -> Self {
-        Self {
-            arena: Arena::new(),
-        }
-    }
-
-    pub fn create_smtype_slot(&self, name: String) -> Symbol {
-        Symbol(self.arena.allocate(Symbol1::SmTypeSlot(Rc::new(SmTypeSlot1 {
-            name,
-            inherits: RefCell::new(None),
-            subtypes: shared_array![],
-            fields: shared_map![],
-            methods: shared_map![],
-            method_output: Rc::new(RefCell::new(proc_macro2::TokenStream::new())),
-        }))))
-    }
-
-    pub fn create_field_slot(&self, is_ref: bool, name: String, field_type: syn::Type, field_init: syn::Expr) -> Symbol {
-        Symbol(self.arena.allocate(Symbol1::FieldSlot(Rc::new(FieldSlot1 {
-            is_ref,
-            name,
-            field_type,
-            field_init,
-        }))))
-    }
-
-    pub fn create_method_slot(&self, name: String, defined_in: Symbol, doc_attribute: Vec<syn::Attribute>) -> Symbol {
-        Symbol(self.arena.allocate(Symbol1::MethodSlot(Rc::new(MethodSlot1 {
-            name,
-            defined_in,
-            doc_attribute: RefCell::new(doc_attribute),
-            override_logic_mapping: SharedMap::new(),
-        }))))
-    }
-}
-
-#[derive(Clone)]
-pub struct Symbol(Weak<Symbol1>);
-
-impl Eq for Symbol {}
-
-impl PartialEq for Symbol {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.ptr_eq(&other.0)
-    }
-}
-
-impl Hash for Symbol {
-    /// Performs hashing of the symbol by reference.
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.0.as_ptr().hash(state)
-    }
-}
-
-macro_rules! access {
-    ($symbol:expr) => { $symbol.0.upgrade().unwrap().as_ref() };
-}
-
-impl Symbol {
-    pub fn is_smtype_slot(&self) -> bool {
-        matches!(access!(self), Symbol1::SmTypeSlot(_))
-    }
-
-    pub fn is_field_slot(&self) -> bool {
-        matches!(access!(self), Symbol1::FieldSlot(_))
-    }
-
-    pub fn is_method_slot(&self) -> bool {
-        matches!(access!(self), Symbol1::MethodSlot(_))
-    }
-
-    pub fn name(&self) -> String {
-        match access!(self) {
-            Symbol1::SmTypeSlot(slot) => slot.name.clone(),
-            Symbol1::FieldSlot(slot) => slot.name.clone(),
-            Symbol1::MethodSlot(slot) => slot.name.clone(),
-        }
-    }
-
-    pub fn inherits(&self) -> Option<Symbol> {
-        match access!(self) {
-            Symbol1::SmTypeSlot(slot) => slot.inherits.borrow().clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn set_inherits(&self, value: Option<&Symbol>) {
-        match access!(self) {
-            Symbol1::SmTypeSlot(slot) => {
-                slot.inherits.replace(value.map(|v| v.clone()));
-            },
-            _ => panic!(),
-        }
-    }
-
-    pub fn asc_smtype_list(&self) -> Vec<Symbol> {
-        let mut out = vec![self.clone()];
-        let mut m = self.inherits();
-        while let Some(m1) = m {
-            out.insert(0, m1.clone());
-            m = m1.inherits();
-        }
-        out
-    }
-
-    /// Returns `MN(M2(M1(...)))` layers over a root `Weak<#DATA::FirstM>` value.
-    /// 
-    /// Parameters:
-    /// 
-    /// * `base`: A `Weak<#DATA::FirstM>` value.
-    pub fn create_layers_over_weak_root(base: &str, asc_smtype_list: &[Symbol]) -> String {
-        let mut layers = String::new();
-        let mut parens = 0usize;
-        for m in asc_smtype_list.iter().rev() {
-            let m_name = m.name();
-            layers.push_str(&format!("{m_name}("));
-            parens += 1;
-        }
-        layers.push_str(base);
-        layers.push_str(".clone()");
-        layers.push_str(&")".repeat(parens));
-        return layers;
-    }
-
-    pub fn lookup_method_in_base_smtype(&self, name: &str) -> Option<Symbol> {
-        let mut m = self.clone();
-        while let Some(m1) = m.inherits() {
-            let mt = m1.methods().get(&name.to_owned());
-            if let Some(mt) = mt {
-                return Some(mt);
-            }
-            m = m1;
-        }
-        None
-    }
-
-    pub fn subtypes(&self) -> SharedArray<Symbol> {
-        match access!(self) {
-            Symbol1::SmTypeSlot(slot) => slot.subtypes.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn fields(&self) -> SharedMap<String, Symbol> {
-        match access!(self) {
-            Symbol1::SmTypeSlot(slot) => slot.fields.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn methods(&self) -> SharedMap<String, Symbol> {
-        match access!(self) {
-            Symbol1::SmTypeSlot(slot) => slot.methods.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn method_output(&self) -> Rc<RefCell<proc_macro2::TokenStream>> {
-        match access!(self) {
-            Symbol1::SmTypeSlot(slot) => slot.method_output.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn field_type(&self) -> syn::Type {
-        match access!(self) {
-            Symbol1::FieldSlot(slot) => slot.field_type.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn field_init(&self) -> syn::Expr {
-        match access!(self) {
-            Symbol1::FieldSlot(slot) => slot.field_init.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn is_ref(&self) -> bool {
-        match access!(self) {
-            Symbol1::FieldSlot(slot) => slot.is_ref.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn defined_in(&self) -> Symbol {
-        match access!(self) {
-            Symbol1::MethodSlot(slot) => slot.defined_in.clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn doc_attribute(&self) -> Vec<syn::Attribute> {
-        match access!(self) {
-            Symbol1::MethodSlot(slot) => slot.doc_attribute.borrow().clone(),
-            _ => panic!(),
-        }
-    }
-
-    pub fn set_doc_attribute(&self, attr: Vec<syn::Attribute>) {
-        match access!(self) {
-            Symbol1::MethodSlot(slot) => { slot.doc_attribute.replace(attr); },
-            _ => panic!(),
-        }
-    }
-
-    pub fn override_logic_mapping(&self) -> SharedMap<Symbol, Rc<OverrideLogicMapping>> {
-        match access!(self) {
-            Symbol1::MethodSlot(slot) => slot.override_logic_mapping.clone(),
-            _ => panic!(),
-        }
-    }
-}
-
-impl ToString for Symbol {
-    fn to_string(&self) -> String {
-        self.name()
-    }
-}
-
-enum Symbol1 {
-    SmTypeSlot(Rc<SmTypeSlot1>),
-    FieldSlot(Rc<FieldSlot1>),
-    MethodSlot(Rc<MethodSlot1>),
-}
-
-struct SmTypeSlot1 {
-    name: String,
-    inherits: RefCell<Option<Symbol>>,
-    subtypes: SharedArray<Symbol>,
-    fields: SharedMap<String, Symbol>,
-    methods: SharedMap<String, Symbol>,
-    method_output: Rc<RefCell<proc_macro2::TokenStream>>,
-}
-
-struct FieldSlot1 {
-    name: String,
-    field_type: syn::Type,
-    field_init: syn::Expr,
-    is_ref: bool,
-}
-
-struct MethodSlot1 {
-    name: String,
-    defined_in: Symbol,
-    doc_attribute: RefCell<Vec<syn::Attribute>>,
-    override_logic_mapping: SharedMap<Symbol, Rc<OverrideLogicMapping>>,
-}
-
-pub struct OverrideLogicMapping {
-    override_code: RefCell<Option<proc_macro2::TokenStream>>,
-    override_logic_mapping: SharedMap<Symbol, Rc<OverrideLogicMapping>>,
-}
-
-impl OverrideLogicMapping {
-    pub fn new() -> Self {
-        Self {
-            override_code: RefCell::new(None),
-            override_logic_mapping: SharedMap::new(),
-        }
-    }
-
-    /// Override code; generally a `return` statement with a semicolon.
-    pub fn override_code(&self) -> Option<proc_macro2::TokenStream> {
-        self.override_code.borrow().clone()
-    }
-
-    /// Sets override code; generally a `return` statement with a semicolon.
-    pub fn set_override_code(&self, code: Option<proc_macro2::TokenStream>) {
-        self.override_code.replace(code);
-    }
-
-    /// Mapping from subtype slot to override logic.
-    pub fn override_logic_mapping(&self) -> SharedMap<Symbol, Rc<OverrideLogicMapping>> {
-        self.override_logic_mapping.clone()
-    }
-}
-
-/// A data type slot.
-/// 
-/// # Supported methods
-/// 
-/// * `is_smtype_slot()` — Returns `true`.
-/// * `name()`
-/// * `inherits()`
-/// * `set_inherits()`
-/// * `subtypes()`
-/// * `fields()`
-/// * `methods()`
-/// * `method_output()` — The contents of the `impl` block of the data type.
-#[derive(Clone, Hash, PartialEq, Eq)]
-pub struct SmTypeSlot(pub Symbol);
-
-impl Deref for SmTypeSlot {
-    type Target = Symbol;
-    fn deref(&self) -> &Self::Target {
-        assert!(self.0.is_smtype_slot());
-        &self.0
-    }
-}
-
-/// A field slot.
-/// 
-/// # Supported methods
-/// 
-/// * `is_field_slot()` — Returns `true`.
-/// * `is_ref()`
-/// * `name()`
-/// * `field_type()`
-/// * `field_init()`
-#[derive(Clone, Hash, PartialEq, Eq)]
-pub struct FieldSlot(pub Symbol);
-
-impl Deref for FieldSlot {
-    type Target = Symbol;
-    fn deref(&self) -> &Self::Target {
-        assert!(self.0.is_field_slot());
-        &self.0
-    }
-}
-
-/// A method slot.
-/// 
-/// # Supported methods
-/// 
-/// * `is_method_slot()` — Returns `true`.
-/// * `name()`
-/// * `defined_in()`
-/// * `doc_attribute()`
-/// * `set_doc_attribute()`
-/// * `override_logic_mapping()` — Mapping from subtype slot to override logic.
-#[derive(Clone, Hash, PartialEq, Eq)]
-pub struct MethodSlot(pub Symbol);
-
-impl Deref for MethodSlot {
-    type Target = Symbol;
-    fn deref(&self) -> &Self::Target {
-        assert!(self.0.is_method_slot());
-        &self.0
-    }
+use crate::*;
+
+pub struct Arena<T> {
+    data: RefCell<Vec<Rc<T>>>,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Self {
+            data: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn allocate(&self, value: T) -> Weak<T> {
+        let obj = Rc::new(value);
+        self.data.borrow_mut().push(obj.clone());
+        Rc::downgrade(&obj)
+    }
+}
+
+pub struct LmtFactory {
+    arena: Arena<Symbol1>,
+}
+
+impl LmtFactory {
+    pub fn new() -> Self {
+        Self {
+            arena: Arena::new(),
+        }
+    }
+
+    pub fn create_smtype_slot(&self, name: &Ident, is_abstract: bool, is_sealed: bool, generics: syn::Generics) -> Symbol {
+        Symbol(self.arena.allocate(Symbol1::SmTypeSlot(Rc::new(SmTypeSlot1 {
+            name: name.to_string(),
+            span: name.span(),
+            is_abstract,
+            is_sealed,
+            generics,
+            external_path: None,
+            inherits: RefCell::new(None),
+            inherits_path: RefCell::new(None),
+            subtypes: shared_array![],
+            fields: shared_map![],
+            methods: shared_map![],
+            method_output: Rc::new(RefCell::new(proc_macro2::TokenStream::new())),
+        }))))
+    }
+
+    /// Creates a slot for a data type defined by another `sem!` invocation,
+    /// possibly in another crate, and referred to by `path`.
+    pub fn create_external_smtype_slot(&self, path: syn::Path) -> Symbol {
+        Symbol(self.arena.allocate(Symbol1::SmTypeSlot(Rc::new(SmTypeSlot1 {
+            name: path.segments.last().unwrap().ident.to_string(),
+            span: path.segments.last().unwrap().ident.span(),
+            is_abstract: false,
+            is_sealed: false,
+            generics: syn::Generics::default(),
+            external_path: Some(path),
+            inherits: RefCell::new(None),
+            inherits_path: RefCell::new(None),
+            subtypes: shared_array![],
+            fields: shared_map![],
+            methods: shared_map![],
+            method_output: Rc::new(RefCell::new(proc_macro2::TokenStream::new())),
+        }))))
+    }
+
+    pub fn create_field_slot(&self, is_ref: bool, is_untraced: bool, name: String, field_type: syn::Type, field_init: syn::Expr, attributes: Vec<syn::Attribute>) -> Symbol {
+        Symbol(self.arena.allocate(Symbol1::FieldSlot(Rc::new(FieldSlot1 {
+            is_ref,
+            is_untraced,
+            name,
+            field_type,
+            field_init,
+            attributes,
+        }))))
+    }
+
+    pub fn create_method_slot(&self, name: String, defined_in: Symbol, doc_attribute: Vec<syn::Attribute>, signature: Rc<SmTypeMethod>) -> Symbol {
+        Symbol(self.arena.allocate(Symbol1::MethodSlot(Rc::new(MethodSlot1 {
+            name,
+            defined_in,
+            is_abstract: signature.is_abstract,
+            signature,
+            doc_attribute: RefCell::new(doc_attribute),
+        }))))
+    }
+}
+
+#[derive(Clone)]
+pub struct Symbol(Weak<Symbol1>);
+
+impl Eq for Symbol {}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
+
+impl Hash for Symbol {
+    /// Performs hashing of the symbol by reference.
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state)
+    }
+}
+
+macro_rules! access {
+    ($symbol:expr) => { $symbol.0.upgrade().unwrap().as_ref() };
+}
+
+impl Symbol {
+    pub fn is_smtype_slot(&self) -> bool {
+        matches!(access!(self), Symbol1::SmTypeSlot(_))
+    }
+
+    pub fn is_field_slot(&self) -> bool {
+        matches!(access!(self), Symbol1::FieldSlot(_))
+    }
+
+    pub fn is_method_slot(&self) -> bool {
+        matches!(access!(self), Symbol1::MethodSlot(_))
+    }
+
+    pub fn name(&self) -> String {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.name.clone(),
+            Symbol1::FieldSlot(slot) => slot.name.clone(),
+            Symbol1::MethodSlot(slot) => slot.name.clone(),
+        }
+    }
+
+    /// Returns the name of the data type as an identifier
+    /// spanning its name where the data type is defined.
+    pub fn name_id(&self) -> Ident {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => Ident::new(&slot.name, slot.span),
+            _ => panic!(),
+        }
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.is_abstract,
+            Symbol1::MethodSlot(slot) => slot.is_abstract,
+            _ => panic!(),
+        }
+    }
+
+    /// Whether the data type is sealed, that is, it
+    /// cannot be extended by another `sem!` invocation.
+    pub fn is_sealed(&self) -> bool {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.is_sealed,
+            _ => panic!(),
+        }
+    }
+
+    /// Whether the data type is defined by another `sem!` invocation.
+    pub fn is_external(&self) -> bool {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.external_path.is_some(),
+            _ => panic!(),
+        }
+    }
+
+    /// Returns the generic parameters of the data type.
+    pub fn generics(&self) -> syn::Generics {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.generics.clone(),
+            _ => panic!(),
+        }
+    }
+
+    /// Whether the data type has generic parameters. An external data type
+    /// is not known to have any.
+    pub fn is_generic(&self) -> bool {
+        !self.generics().params.is_empty()
+    }
+
+    /// Returns the type of the data type in terms of its own generic
+    /// parameters, as in `M<T>`.
+    pub fn self_type(&self) -> proc_macro2::TokenStream {
+        let name = self.name_id();
+        let generics = self.generics();
+        let (_, ty_generics, _) = generics.split_for_impl();
+        quote! { #name #ty_generics }
+    }
+
+    /// Returns an expression wrapping `inner`, a value of the inherited base,
+    /// as a value of the data type.
+    pub fn layer(&self, inner: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let name = self.name_id();
+        let generics = self.generics();
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let turbofish = ty_generics.as_turbofish();
+        if self.inherits().is_some() && generics.type_params().next().is_some() {
+            quote! { #name #turbofish(#inner, ::std::marker::PhantomData) }
+        } else {
+            quote! { #name #turbofish(#inner) }
+        }
+    }
+
+    pub fn inherits(&self) -> Option<Symbol> {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.inherits.borrow().clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn set_inherits(&self, value: Option<&Symbol>) {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => {
+                slot.inherits.replace(value.cloned());
+            },
+            _ => panic!(),
+        }
+    }
+
+    /// Returns the inherited base as written at the data type,
+    /// that is, in terms of the data type's generic parameters.
+    pub fn inherits_path(&self) -> Option<syn::Path> {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.inherits_path.borrow().clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn set_inherits_path(&self, value: Option<syn::Path>) {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => {
+                slot.inherits_path.replace(value);
+            },
+            _ => panic!(),
+        }
+    }
+
+    /// Returns the path to an item defined alongside the inherited base,
+    /// whose name is `prefix` followed by the base's name, with the
+    /// generic arguments given to the base.
+    pub fn inherits_sibling_path(&self, prefix: &str) -> Option<proc_macro2::TokenStream> {
+        let mut path = self.inherits_path()?;
+        let last = path.segments.last_mut().unwrap();
+        last.ident = Ident::new(&format!("{prefix}{}", last.ident), last.ident.span());
+        Some(path.to_token_stream())
+    }
+
+    /// Returns the inherited base types of the data type, from the nearest
+    /// one upwards, each in terms of the data type's generic parameters.
+    /// An external data type ends the list, since its bases are not known.
+    pub fn base_types(&self) -> Vec<(Symbol, syn::Path)> {
+        let mut out = vec![];
+        let mut m = self.clone();
+        let mut path = self.inherits_path();
+        while let (Some(m1), Some(path1)) = (m.inherits(), path) {
+            out.push((m1.clone(), path1.clone()));
+            if m1.is_external() {
+                break;
+            }
+            path = m1.inherits_path().map(|p| {
+                let arguments = &path1.segments.last().unwrap().arguments;
+                syn::parse2(substitute_generics(p.to_token_stream(), &m1.generics(), arguments)).unwrap()
+            });
+            m = m1;
+        }
+        out
+    }
+
+    /// Substitutes the generic parameters of `ancestor`, one of the
+    /// base data types, in a token sequence, so that the sequence is in terms
+    /// of the data type's own generic parameters.
+    pub fn substitute_ancestor_generics(&self, ancestor: &Symbol, tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let Some((_, path)) = self.base_types().into_iter().find(|(m, _)| m == ancestor) else {
+            return tokens;
+        };
+        substitute_generics(tokens, &ancestor.generics(), &path.segments.last().unwrap().arguments)
+    }
+
+    pub fn asc_smtype_list(&self) -> Vec<Symbol> {
+        let mut out = vec![self.clone()];
+        let mut m = self.inherits();
+        while let Some(m1) = m {
+            out.insert(0, m1.clone());
+            m = m1.inherits();
+        }
+        out
+    }
+
+    /// Returns the ascending list of data types defined by the
+    /// same `sem!` invocation, that is, without an external base.
+    pub fn local_asc_smtype_list(&self) -> Vec<Symbol> {
+        self.asc_smtype_list().into_iter().filter(|m| !m.is_external()).collect()
+    }
+
+    /// Returns the external data type the data type's hierarchy extends, if any.
+    pub fn extension_base(&self) -> Option<Symbol> {
+        self.asc_smtype_list().into_iter().find(|m| m.is_external())
+    }
+
+    /// Whether the data type starts a segment: a chain of data types whose data
+    /// are allocated at once, each holding the data of its base inline. That is the case unless the
+    /// data type inherits a local base and neither of them is generic;
+    /// otherwise its data is held by the inherited base as extension data.
+    pub fn is_segment_root(&self) -> bool {
+        match self.inherits() {
+            Some(m) => m.is_external() || m.is_generic() || self.is_generic(),
+            None => true,
+        }
+    }
+
+    /// Returns the ascending list of data types of the data type's segment.
+    pub fn segment_asc_smtype_list(&self) -> Vec<Symbol> {
+        let mut out = vec![self.clone()];
+        let mut m = self.clone();
+        while !m.is_segment_root() {
+            m = m.inherits().unwrap();
+            out.insert(0, m.clone());
+        }
+        out
+    }
+
+    /// Returns the subtypes that belong to the data type's segment.
+    pub fn segment_subtypes(&self) -> Vec<Symbol> {
+        self.subtypes().iter().filter(|m| !m.is_segment_root()).collect()
+    }
+
+    /// Returns the data type and its subtypes that belong to its segment,
+    /// in depth-first order.
+    pub fn segment_smtype_list(&self) -> Vec<Symbol> {
+        let mut out = vec![self.clone()];
+        for m in self.segment_subtypes() {
+            out.extend(m.segment_smtype_list());
+        }
+        out
+    }
+
+    /// Returns the type tag identifying the data type as the most specific
+    /// data type of an instance within its segment. If `extension` is true,
+    /// the tag additionally identifies that the instance holds extension data.
+    pub fn segment_tag(&self, extension: bool) -> usize {
+        let first = &self.segment_asc_smtype_list()[0];
+        let index = first.segment_smtype_list().iter().position(|m| m == self).unwrap();
+        index * 2 + usize::from(extension)
+    }
+
+    /// Whether an instance of the data type may hold the extension data of a subtype,
+    /// that is, whether the data type is not sealed or has a generic subtype.
+    pub fn is_extensible(&self) -> bool {
+        !self.is_sealed() || self.subtypes().iter().any(|m| m.is_segment_root())
+    }
+
+    /// Whether the data of the first data type of the data type's segment holds
+    /// extension data, that is, whether a data type of the segment is extensible.
+    pub fn segment_holds_extension(&self) -> bool {
+        self.segment_asc_smtype_list()[0].segment_smtype_list().iter().any(|m| m.is_extensible())
+    }
+
+    /// Returns the data type holding the data of the data type's segment
+    /// as extension data, if any.
+    pub fn segment_base(&self) -> Option<Symbol> {
+        self.segment_asc_smtype_list()[0].inherits()
+    }
+
+    /// Returns `MN(M2(M1(...)))` layers over a root `Weak<#DATA::FirstM>` value,
+    /// or over the segment base value if the segment has a base.
+    /// 
+    /// Parameters:
+    /// 
+    /// * `base`: A `Weak<#DATA::FirstM>` value or a segment base value.
+    /// * `asc_smtype_list`: The ascending list of data types of a segment.
+    pub fn create_layers_over_weak_root(base: &proc_macro2::TokenStream, asc_smtype_list: &[Symbol]) -> proc_macro2::TokenStream {
+        let mut layers = quote! { #base.clone() };
+        for m in asc_smtype_list.iter() {
+            layers = m.layer(&layers);
+        }
+        layers
+    }
+
+    /// Looks up a method in the data type or, if it does not define it,
+    /// in one of the base data types.
+    pub fn lookup_method(&self, name: &str) -> Option<Symbol> {
+        if let Some(mt) = self.methods().get(&name.to_owned()) {
+            return Some(mt);
+        }
+        self.lookup_method_in_base_smtype(name)
+    }
+
+    pub fn lookup_method_in_base_smtype(&self, name: &str) -> Option<Symbol> {
+        let mut m = self.clone();
+        while let Some(m1) = m.inherits() {
+            let mt = m1.methods().get(&name.to_owned());
+            if let Some(mt) = mt {
+                return Some(mt);
+            }
+            m = m1;
+        }
+        None
+    }
+
+    pub fn subtypes(&self) -> SharedArray<Symbol> {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.subtypes.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn fields(&self) -> SharedMap<String, Symbol> {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.fields.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn methods(&self) -> SharedMap<String, Symbol> {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.methods.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn method_output(&self) -> Rc<RefCell<proc_macro2::TokenStream>> {
+        match access!(self) {
+            Symbol1::SmTypeSlot(slot) => slot.method_output.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn field_type(&self) -> syn::Type {
+        match access!(self) {
+            Symbol1::FieldSlot(slot) => slot.field_type.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn field_init(&self) -> syn::Expr {
+        match access!(self) {
+            Symbol1::FieldSlot(slot) => slot.field_init.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn field_attributes(&self) -> Vec<syn::Attribute> {
+        match access!(self) {
+            Symbol1::FieldSlot(slot) => slot.attributes.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn is_ref(&self) -> bool {
+        match access!(self) {
+            Symbol1::FieldSlot(slot) => slot.is_ref.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn is_untraced(&self) -> bool {
+        match access!(self) {
+            Symbol1::FieldSlot(slot) => slot.is_untraced,
+            _ => panic!(),
+        }
+    }
+
+    pub fn defined_in(&self) -> Symbol {
+        match access!(self) {
+            Symbol1::MethodSlot(slot) => slot.defined_in.clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn doc_attribute(&self) -> Vec<syn::Attribute> {
+        match access!(self) {
+            Symbol1::MethodSlot(slot) => slot.doc_attribute.borrow().clone(),
+            _ => panic!(),
+        }
+    }
+
+    pub fn set_doc_attribute(&self, attr: Vec<syn::Attribute>) {
+        match access!(self) {
+            Symbol1::MethodSlot(slot) => { slot.doc_attribute.replace(attr); },
+            _ => panic!(),
+        }
+    }
+
+    pub fn signature(&self) -> Rc<SmTypeMethod> {
+        match access!(self) {
+            Symbol1::MethodSlot(slot) => slot.signature.clone(),
+            _ => panic!(),
+        }
+    }
+
+}
+
+impl ToString for Symbol {
+    fn to_string(&self) -> String {
+        self.name()
+    }
+}
+
+enum Symbol1 {
+    SmTypeSlot(Rc<SmTypeSlot1>),
+    FieldSlot(Rc<FieldSlot1>),
+    MethodSlot(Rc<MethodSlot1>),
+}
+
+struct SmTypeSlot1 {
+    name: String,
+    span: Span,
+    is_abstract: bool,
+    is_sealed: bool,
+    generics: syn::Generics,
+    external_path: Option<syn::Path>,
+    inherits: RefCell<Option<Symbol>>,
+    inherits_path: RefCell<Option<syn::Path>>,
+    subtypes: SharedArray<Symbol>,
+    fields: SharedMap<String, Symbol>,
+    methods: SharedMap<String, Symbol>,
+    method_output: Rc<RefCell<proc_macro2::TokenStream>>,
+}
+
+struct FieldSlot1 {
+    name: String,
+    field_type: syn::Type,
+    field_init: syn::Expr,
+    is_ref: bool,
+    is_untraced: bool,
+    attributes: Vec<syn::Attribute>,
+}
+
+struct MethodSlot1 {
+    name: String,
+    defined_in: Symbol,
+    is_abstract: bool,
+    signature: Rc<SmTypeMethod>,
+    doc_attribute: RefCell<Vec<syn::Attribute>>,
+}
+
+/// A data type slot.
+/// 
+/// # Supported methods
+/// 
+/// * `is_smtype_slot()` — Returns `true`.
+/// * `name()`
+/// * `name_id()`
+/// * `is_abstract()`
+/// * `is_sealed()`
+/// * `is_external()`
+/// * `generics()`
+/// * `is_generic()`
+/// * `self_type()`
+/// * `layer()`
+/// * `inherits()`
+/// * `set_inherits()`
+/// * `inherits_path()`
+/// * `set_inherits_path()`
+/// * `inherits_sibling_path()`
+/// * `base_types()`
+/// * `substitute_ancestor_generics()`
+/// * `is_segment_root()`
+/// * `segment_asc_smtype_list()`
+/// * `segment_subtypes()`
+/// * `segment_smtype_list()`
+/// * `segment_tag()`
+/// * `is_extensible()`
+/// * `segment_holds_extension()`
+/// * `segment_base()`
+/// * `subtypes()`
+/// * `fields()`
+/// * `methods()`
+/// * `method_output()` — The contents of the `impl` block of the data type.
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct SmTypeSlot(pub Symbol);
+
+impl Deref for SmTypeSlot {
+    type Target = Symbol;
+    fn deref(&self) -> &Self::Target {
+        assert!(self.0.is_smtype_slot());
+        &self.0
+    }
+}
+
+/// A field slot.
+/// 
+/// # Supported methods
+/// 
+/// * `is_field_slot()` — Returns `true`.
+/// * `is_ref()`
+/// * `is_untraced()`
+/// * `name()`
+/// * `field_type()`
+/// * `field_init()`
+/// * `field_attributes()`
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FieldSlot(pub Symbol);
+
+impl Deref for FieldSlot {
+    type Target = Symbol;
+    fn deref(&self) -> &Self::Target {
+        assert!(self.0.is_field_slot());
+        &self.0
+    }
+}
+
+/// A method slot.
+/// 
+/// # Supported methods
+/// 
+/// * `is_method_slot()` — Returns `true`.
+/// * `name()`
+/// * `defined_in()`
+/// * `is_abstract()`
+/// * `signature()`
+/// * `doc_attribute()`
+/// * `set_doc_attribute()`
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct MethodSlot(pub Symbol);
+
+impl Deref for MethodSlot {
+    type Target = Symbol;
+    fn deref(&self) -> &Self::Target {
+        assert!(self.0.is_method_slot());
+        &self.0
+    }
 }
\ No newline at end of file
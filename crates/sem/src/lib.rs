@@ -1,178 +1,2426 @@
-use std::{cell::RefCell, rc::{Rc, Weak}};
-use std::fmt::Debug;
-
-pub mod util;
-
-pub use hydroperx_sem_proc::sem;
-
-pub struct Arena<T> {
-    data: RefCell<Vec<Rc<T>>>,
-}
-
-impl<T> Arena<T> {
-    pub fn new() -> Self {
-        Self {
-            data: RefCell::new(vec![]),
-        }
-    }
-
-    pub fn allocate(&self, value: T) -> Weak<T> {
-        let obj = Rc::new(value);
-        self.data.borrow_mut().push(obj.clone());
-        Rc::downgrade(&obj)
-    }
-
-    /// Frees dead objects from the arena. Note that a call to `clean()`
-    /// may be expensive; therefore it is recommended to call it after a long
-    /// processing has been done with the arena.
-    pub fn clean(&self) {
-        let mut data = self.data.borrow_mut();
-        let mut i = data.len();
-        while i != 0 {
-            i -= 1;
-            let obj = data.get(i).unwrap();
-            if Rc::weak_count(obj) == 0 && Rc::strong_count(obj) == 1 {
-                data.remove(i);
-            }
-        }
-    }
-}
-
-#[derive(Debug)]
-pub enum SemError {
-    Contravariant,
-}
-
-#[cfg(test)]
-mod test {
-    #[test]
-    fn test() {
-        use crate::sem;
-
-        sem! {
-            mod sem = crate;
-
-            type Arena = Arena;
-        
-            /// My unified data type.
-            struct Entity {
-                pub fn Entity() {
-                    super();
-                }
-        
-                /// Empty, Foo, FooBar or FooQux
-                pub fn name(&self) -> String {
-                    "".into()
-                }
-
-                pub fn base_example(&self) -> String {
-                    "from base".into()
-                }
-
-                pub fn x(&self) -> f64 {
-                    0.0
-                }
-            }
-        
-            struct Foo: Entity {
-                pub fn Foo() {
-                    super();
-                }
-
-                #[inheritdoc]
-                pub override fn name(&self) -> String {
-                    "Foo".into()
-                }
-            }
-        
-            struct FooBar: Foo {
-                pub fn FooBar() {
-                    super();
-                }
-
-                #[inheritdoc]
-                pub override fn name(&self) -> String {
-                    "FooBar".into()
-                }
-
-                pub override fn base_example(&self) -> String {
-                    format!("from bar; {}", super.base_example())
-                }
-            }
-            
-            struct FooBarBar: FooBar {
-                let m_x: f64 = 0.0;
-                let ref m_y: String = "".into();
-
-                pub fn FooBarBar(x: f64, y: &str) {
-                    super();
-                    self.set_m_x(x);
-                    self.set_m_y(y.into());
-                }
-
-                #[inheritdoc]
-                pub override fn name(&self) -> String {
-                    "FooBarBar".into()
-                }
-
-                pub override fn x(&self) -> f64 {
-                    self.m_x()
-                }
-
-                pub override fn base_example(&self) -> String {
-                    format!("from {}; {}", self.m_y(), super.base_example())
-                }
-            }
-        
-            struct FooQux: Foo {
-                pub fn FooQux() {
-                    super();
-                }
-
-                #[inheritdoc]
-                pub override fn name(&self) -> String {
-                    "FooQux".into()
-                }
-            }
-        }
-
-        let arena = Arena::new();
-
-        let symbol = Foo::new(&arena);
-        let base_symbol: Entity = symbol.into();
-        assert_eq!("Foo", base_symbol.name());
-        assert_eq!(true, base_symbol.is::<Foo>());
-        assert_eq!(false, base_symbol.is::<FooBar>());
-        assert_eq!(false, base_symbol.is::<FooQux>());
-        assert_eq!("from base", base_symbol.base_example());
-        assert_eq!(0.0, base_symbol.x());
-
-        let symbol = FooBar::new(&arena);
-        let base_symbol: Entity = symbol.into();
-        assert_eq!("FooBar", base_symbol.name());
-        assert_eq!(true, base_symbol.is::<Foo>());
-        assert_eq!(true, base_symbol.is::<FooBar>());
-        assert_eq!(false, base_symbol.is::<FooBarBar>());
-        assert_eq!(false, base_symbol.is::<FooQux>());
-        assert_eq!("from bar; from base", base_symbol.base_example());
-        assert_eq!(0.0, base_symbol.x());
-
-        let symbol = FooBarBar::new(&arena, 10.0, "bar bar");
-        let base_symbol: Entity = symbol.into();
-        assert_eq!("FooBarBar", base_symbol.name());
-        assert_eq!(true, base_symbol.is::<Foo>());
-        assert_eq!(true, base_symbol.is::<FooBar>());
-        assert_eq!(true, base_symbol.is::<FooBarBar>());
-        assert_eq!(false, base_symbol.is::<FooQux>());
-        assert_eq!("from bar bar; from bar; from base", base_symbol.base_example());
-        assert_eq!(10.0, base_symbol.x());
-
-        let symbol = FooQux::new(&arena);
-        let base_symbol: Entity = symbol.into();
-        assert_eq!("FooQux", base_symbol.name());
-        assert_eq!(true, base_symbol.is::<Foo>());
-        assert_eq!(false, base_symbol.is::<FooBar>());
-        assert_eq!(true, base_symbol.is::<FooQux>());
-        assert_eq!(0.0, base_symbol.x());
-    }
+use std::{any::{Any, TypeId}, cell::{Cell, Ref, RefCell}, marker::PhantomData, ops::Deref, rc::{Rc, Weak}};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+
+/// Implements the `Trace` trait in scope for the standard types whose
+/// implementation is the same under every `Handle` mode, that is, all of
+/// them but the cells and `Rc`. The crate root and the `sync` module,
+/// which define a `Trace` trait and a `Tracer` each, invoke it.
+macro_rules! impl_std_trace {
+    () => {
+        impl<T: Trace> Trace for Option<T> {
+            fn trace(&self, tracer: &mut Tracer) {
+                if let Some(v) = self {
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<T: Trace> Trace for Vec<T> {
+            fn trace(&self, tracer: &mut Tracer) {
+                for v in self {
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<T: Trace + ?Sized> Trace for Box<T> {
+            fn trace(&self, tracer: &mut Tracer) {
+                (**self).trace(tracer);
+            }
+        }
+
+        impl<T: Trace + ?Sized> Trace for ::std::sync::Arc<T> {
+            fn trace(&self, tracer: &mut Tracer) {
+                (**self).trace(tracer);
+            }
+        }
+
+        impl<T: Trace + ?Sized> Trace for &T {
+            fn trace(&self, tracer: &mut Tracer) {
+                (**self).trace(tracer);
+            }
+        }
+
+        impl<T: Trace, E: Trace> Trace for Result<T, E> {
+            fn trace(&self, tracer: &mut Tracer) {
+                match self {
+                    Ok(v) => v.trace(tracer),
+                    Err(e) => e.trace(tracer),
+                }
+            }
+        }
+
+        impl<T: Trace> Trace for [T] {
+            fn trace(&self, tracer: &mut Tracer) {
+                for v in self {
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<T: Trace, const N: usize> Trace for [T; N] {
+            fn trace(&self, tracer: &mut Tracer) {
+                self.as_slice().trace(tracer);
+            }
+        }
+
+        impl<T: Trace> Trace for ::std::collections::VecDeque<T> {
+            fn trace(&self, tracer: &mut Tracer) {
+                for v in self {
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<T: Trace, S> Trace for ::std::collections::HashSet<T, S> {
+            fn trace(&self, tracer: &mut Tracer) {
+                for v in self {
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<T: Trace> Trace for ::std::collections::BTreeSet<T> {
+            fn trace(&self, tracer: &mut Tracer) {
+                for v in self {
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<K: Trace, V: Trace, S> Trace for ::std::collections::HashMap<K, V, S> {
+            fn trace(&self, tracer: &mut Tracer) {
+                for (k, v) in self {
+                    k.trace(tracer);
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<K: Trace, V: Trace> Trace for ::std::collections::BTreeMap<K, V> {
+            fn trace(&self, tracer: &mut Tracer) {
+                for (k, v) in self {
+                    k.trace(tracer);
+                    v.trace(tracer);
+                }
+            }
+        }
+
+        impl<T: ?Sized> Trace for ::std::marker::PhantomData<T> {
+            fn trace(&self, _tracer: &mut Tracer) {}
+        }
+
+        impl_trace_tuple!(A);
+        impl_trace_tuple!(A, B);
+        impl_trace_tuple!(A, B, C);
+        impl_trace_tuple!(A, B, C, D);
+        impl_trace_tuple!(A, B, C, D, E);
+        impl_trace_tuple!(A, B, C, D, E, F);
+        impl_trace_tuple!(A, B, C, D, E, F, G);
+        impl_trace_tuple!(A, B, C, D, E, F, G, H);
+
+        impl_trace_leaf!(bool, char, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, (), str, String);
+    };
+}
+
+macro_rules! impl_trace_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: Trace),+> Trace for ($($t,)+) {
+            #[allow(non_snake_case)]
+            fn trace(&self, tracer: &mut Tracer) {
+                let ($($t,)+) = self;
+                $($t.trace(tracer);)+
+            }
+        }
+    };
+}
+
+/// Implements `Trace` for types holding no entities, so that
+/// containers keyed or indexed by them are traced.
+macro_rules! impl_trace_leaf {
+    ($($t:ty),*) => {
+        $(
+            impl Trace for $t {
+                fn trace(&self, _tracer: &mut Tracer) {}
+            }
+        )*
+    };
+}
+
+pub mod sync;
+pub mod util;
+
+pub use hydroperx_sem_proc::{sem, sem_extend};
+
+/// An arena holding the data of all root hierarchies of
+/// a `sem!` invocation.
+pub struct Arena {
+    data: RefCell<Objects<Rc<dyn Any>>>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self {
+            data: RefCell::new(Objects::new()),
+        }
+    }
+
+    /// Allocates the data of an entity of the data type `D`.
+    pub fn allocate<D: DataType, T: 'static>(&self, value: T) -> Weak<T> {
+        let obj = Rc::new(value);
+        self.data.borrow_mut().insert::<D>(Rc::as_ptr(&obj) as *const () as usize, obj.clone());
+        Rc::downgrade(&obj)
+    }
+
+    /// The number of objects in the arena.
+    pub fn len(&self) -> usize {
+        self.data.borrow().len()
+    }
+
+    /// Whether the arena holds no objects.
+    pub fn is_empty(&self) -> bool {
+        self.data.borrow().len() == 0
+    }
+
+    /// Iterates the entities of the arena that are of the data type `T`, in
+    /// allocation order. With the basemost data type of a hierarchy, all
+    /// entities of the hierarchy are iterated.
+    ///
+    /// The iteration sees the entities allocated before it starts
+    /// and not freed before it reaches them.
+    pub fn iter_of<T: DataType>(&self) -> impl Iterator<Item = T> + '_ where T::Root: FromObject<Rc<dyn Any>> {
+        let mut cursor = self.data.borrow().cursor::<T>();
+        std::iter::from_fn(move || loop {
+            let obj = self.data.borrow().next(&mut cursor)?;
+            if let Some(entity) = T::Root::from_object(&obj).and_then(|root| T::from_root(root).ok()) {
+                return Some(entity);
+            }
+        })
+    }
+
+    /// The number of entities of the arena that are of the data type `T`.
+    pub fn count_of<T: DataType>(&self) -> usize {
+        self.data.borrow().count::<T>()
+    }
+
+    /// Creates a region: a child arena whose entities may refer to the entities
+    /// of this arena and are freed at once when the region is dropped or reset.
+    pub fn region(&self) -> Region<'_, Self> {
+        Region::new(Self::new(), self)
+    }
+
+    /// Frees all entities of the arena at once.
+    pub fn reset(&self) {
+        // The objects are dropped after the arena is released,
+        // since dropping an object may access the arena.
+        let data = self.data.borrow_mut().take();
+        drop(data);
+    }
+
+    /// Frees an entity right away, returning whether it was held by the arena.
+    /// Later accesses to the entity fail with `SemError::Dropped`.
+    ///
+    /// Freeing an entity takes time logarithmic in the number of objects of the arena.
+    pub fn free<T: DataType>(&self, entity: &T) -> bool where T::Root: FromObject<Rc<dyn Any>> {
+        let root = entity.clone().into_root();
+        let mut data = self.data.borrow_mut();
+        let Some(obj) = data.remove(root.object_key()) else {
+            return false;
+        };
+        // The object is dropped after the arena is released,
+        // since dropping an object may access the arena.
+        drop(data);
+        drop(obj);
+        true
+    }
+
+    /// Frees dead objects from the arena, that is, objects to which no handle
+    /// remains. Note that a call to `clean()` may be expensive; therefore it is
+    /// recommended to call it after a long processing has been done with the arena.
+    pub fn clean(&self) {
+        self.sweep(|obj| Rc::weak_count(obj) != 0 || Rc::strong_count(obj) != 1);
+    }
+
+    /// Frees the objects unreachable from `roots`, following the entities held by
+    /// the fields of reachable entities, including in reference cycles. The objects
+    /// are marked from the roots, then swept in a single pass over the arena.
+    pub fn collect(&self, roots: &[&dyn Trace]) {
+        let mut tracer = Tracer::new();
+        for root in roots {
+            root.trace(&mut tracer);
+        }
+        while let Some(data) = tracer.pending.pop() {
+            data.trace(&mut tracer);
+        }
+        self.sweep(|obj| tracer.marked.contains(&Rc::as_ptr(obj).cast::<()>()));
+    }
+
+    /// Frees the objects not retained by `f`.
+    fn sweep(&self, f: impl FnMut(&Rc<dyn Any>) -> bool) {
+        // The objects are dropped after the arena is released,
+        // since dropping an object may access the arena.
+        let dead = self.data.borrow_mut().retain(f);
+        drop(dead);
+    }
+}
+
+/// The objects of an arena in allocation order, found by their
+/// keys and counted by the data types of their entities.
+struct Objects<O> {
+    /// The objects by allocation sequence number.
+    entries: BTreeMap<u64, Entry<O>>,
+    /// The allocation sequence numbers by object key.
+    keys: HashMap<usize, u64>,
+    next: u64,
+    /// The number of objects of each data type, including subtypes.
+    counts: HashMap<TypeId, usize>,
+    /// The data type and the base data types of each data type allocated.
+    type_ids: HashMap<TypeId, Vec<TypeId>>,
+}
+
+struct Entry<O> {
+    object: O,
+    /// Identifies the object within the arena, as `FromObject::object_key()`.
+    key: usize,
+    /// The data type of the entity.
+    type_id: TypeId,
+}
+
+/// A position in the objects of an arena, iterating the objects allocated
+/// before its creation whose entity is of a given data type.
+struct Cursor {
+    next: u64,
+    end: u64,
+    type_id: TypeId,
+}
+
+impl<O: Clone> Objects<O> {
+    fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            keys: HashMap::new(),
+            next: 0,
+            counts: HashMap::new(),
+            type_ids: HashMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn count<T: DataType>(&self) -> usize {
+        self.counts.get(&TypeId::of::<T>()).copied().unwrap_or(0)
+    }
+
+    fn insert<D: DataType>(&mut self, key: usize, object: O) {
+        let type_id = TypeId::of::<D>();
+        for t in self.type_ids.entry(type_id).or_insert_with(D::type_ids).iter() {
+            *self.counts.entry(*t).or_default() += 1;
+        }
+        self.entries.insert(self.next, Entry { object, key, type_id });
+        self.keys.insert(key, self.next);
+        self.next += 1;
+    }
+
+    fn remove_entry(&mut self, seq: u64) -> O {
+        let entry = self.entries.remove(&seq).unwrap();
+        self.keys.remove(&entry.key);
+        for t in self.type_ids[&entry.type_id].iter() {
+            *self.counts.get_mut(t).unwrap() -= 1;
+        }
+        entry.object
+    }
+
+    /// Removes the object of the given key, if any.
+    fn remove(&mut self, key: usize) -> Option<O> {
+        let seq = *self.keys.get(&key)?;
+        Some(self.remove_entry(seq))
+    }
+
+    /// Removes the objects not retained by `f`, returning them.
+    fn retain(&mut self, mut f: impl FnMut(&O) -> bool) -> Vec<O> {
+        let dead = self.entries.iter().filter(|(_, entry)| !f(&entry.object)).map(|(seq, _)| *seq).collect::<Vec<_>>();
+        dead.into_iter().map(|seq| self.remove_entry(seq)).collect()
+    }
+
+    /// Removes all objects, returning them.
+    fn take(&mut self) -> Vec<O> {
+        self.keys.clear();
+        self.counts.clear();
+        std::mem::take(&mut self.entries).into_values().map(|entry| entry.object).collect()
+    }
+
+    fn cursor<T: DataType>(&self) -> Cursor {
+        Cursor { next: 0, end: self.next, type_id: TypeId::of::<T>() }
+    }
+
+    /// Returns the next object of the cursor, if any.
+    fn next(&self, cursor: &mut Cursor) -> Option<O> {
+        let (seq, entry) = self.entries.range(cursor.next..cursor.end)
+            .find(|(_, entry)| self.type_ids[&entry.type_id].contains(&cursor.type_id))?;
+        cursor.next = seq + 1;
+        Some(entry.object.clone())
+    }
+}
+
+/// Follows the entities held by a value, for `Arena::collect`.
+///
+/// It is implemented by the data types of the `sem!` and `sem_extend!`
+/// macros, by the shared containers and by common containers
+/// of values implementing it.
+///
+/// The type of each field of a data type must implement it, unless the field
+/// is marked with `#[sem(untraced)]`, in which case `collect()` does not follow
+/// the entities it holds.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not implement `Trace`",
+    note = "mark the field with `#[sem(untraced)]` if it holds no entities that `collect()` must follow",
+)]
+pub trait Trace {
+    /// Marks the entities held by the value as reachable.
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+/// Marks the entities reachable from the roots of `Arena::collect`.
+pub struct Tracer {
+    marked: HashSet<*const ()>,
+    pending: Vec<Rc<dyn Trace>>,
+}
+
+impl Tracer {
+    fn new() -> Self {
+        Self {
+            marked: HashSet::new(),
+            pending: vec![],
+        }
+    }
+
+    /// Marks the data of an entity as reachable, tracing
+    /// its fields unless it has already been marked.
+    #[doc(hidden)]
+    pub fn mark<D: SegmentData + ?Sized>(&mut self, data: Rc<D>) {
+        if self.marked.insert(Rc::as_ptr(&data).cast::<()>()) {
+            self.pending.push(data.into_trace());
+        }
+    }
+}
+
+impl<T: Copy + Trace> Trace for Cell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.get().trace(tracer);
+    }
+}
+
+impl<T: Trace + ?Sized> Trace for RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.borrow().trace(tracer);
+    }
+}
+
+impl<T: Trace + ?Sized> Trace for Rc<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        (**self).trace(tracer);
+    }
+}
+
+impl_std_trace!();
+
+/// A child arena of type `A`, created by the `region()` method of an arena, which
+/// dereferences to `A` and may thus be given to constructors. Its entities may refer
+/// to the entities of the parent arena, which it borrows, and are freed at once when
+/// the region is dropped or reset, without visiting the parent arena.
+pub struct Region<'a, A> {
+    arena: A,
+    parent: &'a A,
+}
+
+impl<'a, A> Region<'a, A> {
+    pub(crate) fn new(arena: A, parent: &'a A) -> Self {
+        Self { arena, parent }
+    }
+
+    /// The parent arena.
+    pub fn parent(&self) -> &'a A {
+        self.parent
+    }
+}
+
+impl<'a, A> Deref for Region<'a, A> {
+    type Target = A;
+    fn deref(&self) -> &A {
+        &self.arena
+    }
+}
+
+thread_local! {
+    /// The slots of all index arenas of the current thread, through
+    /// which an `Index` handle reaches its value without its arena.
+    static SLOTS: RefCell<Slots> = const { RefCell::new(Slots { entries: vec![], free: vec![] }) };
+}
+
+struct Slots {
+    entries: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+impl Slots {
+    /// Releases a slot, returning its value, which is to be dropped
+    /// after the slots are released, since dropping an object may
+    /// access other objects.
+    fn release(&mut self, index: u32) -> Option<Box<dyn Any>> {
+        let slot = &mut self.entries[index as usize];
+        let value = slot.value.take();
+        // A slot whose generation is exhausted is not reused.
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.free.push(index);
+        }
+        value
+    }
+}
+
+struct Slot {
+    generation: u32,
+    value: Option<Box<dyn Any>>,
+}
+
+/// An arena holding the data of all root hierarchies of a `sem!`
+/// invocation whose handles are `Copy` indices (`type Handle = Index;`).
+///
+/// Dropping the arena frees its objects; a handle to a freed
+/// object is detected through the generation of its slot.
+///
+/// The slots of the arena belong to the thread that created it; therefore,
+/// the arena cannot be sent to another thread:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<hydroperx_sem::IndexArena>();
+/// ```
+pub struct IndexArena {
+    indices: RefCell<Objects<u32>>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl IndexArena {
+    pub fn new() -> Self {
+        Self {
+            indices: RefCell::new(Objects::new()),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The number of objects in the arena.
+    pub fn len(&self) -> usize {
+        self.indices.borrow().len()
+    }
+
+    /// Whether the arena holds no objects.
+    pub fn is_empty(&self) -> bool {
+        self.indices.borrow().len() == 0
+    }
+
+    /// Iterates the entities of the arena that are of the data type `T`, in
+    /// allocation order. With the basemost data type of a hierarchy, all
+    /// entities of the hierarchy are iterated.
+    ///
+    /// The iteration sees the entities allocated before it starts
+    /// and not freed before it reaches them.
+    pub fn iter_of<T: DataType>(&self) -> impl Iterator<Item = T> + '_ where T::Root: FromObject<Index<dyn Any>> {
+        let mut cursor = self.indices.borrow().cursor::<T>();
+        std::iter::from_fn(move || loop {
+            let index = self.indices.borrow().next(&mut cursor)?;
+            let generation = SLOTS.with(|slots| slots.borrow().entries[index as usize].generation);
+            if let Some(entity) = T::Root::from_object(&Index::new(index, generation)).and_then(|root| T::from_root(root).ok()) {
+                return Some(entity);
+            }
+        })
+    }
+
+    /// The number of entities of the arena that are of the data type `T`.
+    pub fn count_of<T: DataType>(&self) -> usize {
+        self.indices.borrow().count::<T>()
+    }
+
+    /// Creates a region: a child arena whose entities may refer to the entities
+    /// of this arena and are freed at once when the region is dropped or reset.
+    pub fn region(&self) -> Region<'_, Self> {
+        Region::new(Self::new(), self)
+    }
+
+    /// Frees all entities of the arena at once.
+    pub fn reset(&self) {
+        let indices = self.indices.borrow_mut().take();
+        // The objects are dropped after the slots are released,
+        // since dropping an object may access other objects.
+        let _ = SLOTS.try_with(|slots| {
+            let mut values = vec![];
+            {
+                let mut slots = slots.borrow_mut();
+                for index in indices {
+                    values.push(slots.release(index));
+                }
+            }
+            drop(values);
+        });
+    }
+
+    /// Frees an entity right away, returning whether it was held by the arena.
+    /// Later accesses to the entity fail with `SemError::Dropped`, and its slot
+    /// may be reused.
+    ///
+    /// Freeing an entity takes time logarithmic in the number of objects of the arena.
+    pub fn free<T: DataType>(&self, entity: &T) -> bool where T::Root: FromObject<Index<dyn Any>> {
+        let root = entity.clone().into_root();
+        let mut indices = self.indices.borrow_mut();
+        let Some(value) = SLOTS.with(|slots| {
+            let mut slots = slots.borrow_mut();
+            // A handle to a freed entity may refer to a reused slot.
+            let index = u32::try_from(root.object_key()).ok()?;
+            if !root.is_object(&Index::new(index, slots.entries.get(index as usize)?.generation)) {
+                return None;
+            }
+            indices.remove(index as usize)?;
+            Some(slots.release(index))
+        }) else {
+            return false;
+        };
+        // The object is dropped after the arena is released.
+        drop(indices);
+        drop(value);
+        true
+    }
+
+    /// Allocates the data of an entity of the data type `D`.
+    pub fn allocate<D: DataType, T: ?Sized + 'static>(&self, value: Rc<T>) -> Index<T> {
+        let index = SLOTS.with(|slots| {
+            let mut slots = slots.borrow_mut();
+            let value: Option<Box<dyn Any>> = Some(Box::new(value));
+            if let Some(index) = slots.free.pop() {
+                let slot = &mut slots.entries[index as usize];
+                slot.value = value;
+                Index::new(index, slot.generation)
+            } else {
+                let index = u32::try_from(slots.entries.len()).expect("Too many objects.");
+                slots.entries.push(Slot { generation: 0, value });
+                Index::new(index, 0)
+            }
+        });
+        self.indices.borrow_mut().insert::<D>(index.index as usize, index.index);
+        index
+    }
+}
+
+impl Default for IndexArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for IndexArena {
+    fn drop(&mut self) {
+        self.reset();
+    }
+}
+
+/// A `Copy` handle to an object allocated by an `IndexArena`: the index
+/// of its slot and the generation of the slot, incremented whenever
+/// the slot is freed.
+pub struct Index<T: ?Sized> {
+    index: u32,
+    generation: u32,
+    // Not `Send`, as the slots are local to the thread.
+    _marker: PhantomData<*const T>,
+}
+
+impl<T: ?Sized> Index<T> {
+    fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation, _marker: PhantomData }
+    }
+
+    /// The index of the slot.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The generation of the slot at allocation.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Converts the handle to a handle to an object of another type.
+    #[doc(hidden)]
+    pub fn cast<U: ?Sized>(self) -> Index<U> {
+        Index::new(self.index, self.generation)
+    }
+}
+
+impl<T: ?Sized + 'static> Index<T> {
+    /// Returns the object, or `None` if it has been freed.
+    pub fn upgrade(&self) -> Option<Rc<T>> {
+        SLOTS.with(|slots| {
+            let slots = slots.borrow();
+            let slot = slots.entries.get(self.index as usize).filter(|slot| slot.generation == self.generation)?;
+            slot.value.as_ref()?.downcast_ref::<Rc<T>>().cloned()
+        })
+    }
+}
+
+impl<T: ?Sized> Clone for Index<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Index<T> {}
+
+impl<T: ?Sized> PartialEq for Index<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T: ?Sized> Eq for Index<T> {}
+
+impl<T: ?Sized> Hash for Index<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T: ?Sized> Debug for Index<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Index").field("index", &self.index).field("generation", &self.generation).finish()
+    }
+}
+
+/// Relates a data type to the basemost data type of its hierarchy.
+/// It is implemented by the `sem!` and `sem_extend!` macros.
+pub trait DataType: Clone + Sized + 'static {
+    /// The basemost data type of the hierarchy.
+    type Root: DataType;
+
+    /// The type identifiers of the data type and of its base data types,
+    /// by which the arenas count their entities.
+    #[doc(hidden)]
+    fn type_ids() -> Vec<TypeId>;
+
+    /// Converts the value to the basemost data type.
+    fn into_root(self) -> Self::Root;
+
+    /// Converts a value of the basemost data type to the data type.
+    fn from_root(root: Self::Root) -> Result<Self, SemError>;
+}
+
+/// Converts an object of an arena, of type `O`, to the basemost data
+/// type of its hierarchy. It is implemented by the `sem!` macro.
+#[doc(hidden)]
+pub trait FromObject<O: ?Sized>: Sized {
+    /// Returns the entity of the object, if the object
+    /// belongs to the hierarchy.
+    fn from_object(object: &O) -> Option<Self>;
+
+    /// Whether the object is the entity.
+    fn is_object(&self, object: &O) -> bool;
+
+    /// Identifies the object of the entity within its arena: the address
+    /// of its data, or the index of its slot with index handles.
+    fn object_key(&self) -> usize;
+}
+
+/// A borrow of a `ref` field, returned by the `fieldname_ref()` accessor.
+/// It keeps the data of the field alive while borrowed.
+pub struct FieldRef<'a, T: 'static> {
+    // Dropped before the data.
+    value: Ref<'a, T>,
+    _data: Rc<dyn Any>,
+}
+
+impl<'a, T: 'static> FieldRef<'a, T> {
+    /// Borrows the field projected by `field` from `data`.
+    #[doc(hidden)]
+    pub fn new<D: SegmentData + ?Sized>(data: Rc<D>, field: impl for<'b> FnOnce(&'b D) -> &'b RefCell<T>) -> Self {
+        let cell: *const RefCell<T> = field(&data);
+        // SAFETY: the cell lies within the allocation of `data`, which does not
+        // move and outlives the borrow, as `value` is dropped first.
+        let value = unsafe { &*cell }.borrow();
+        Self { value, _data: data.into_any() }
+    }
+}
+
+impl<'a, T: 'static> Deref for FieldRef<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// The data of the data types of a segment, allocated at once. It is
+/// a supertrait of the data traits generated by the `sem!` macro.
+#[doc(hidden)]
+pub trait SegmentData: Any + Trace + sealed::Sealed {
+    /// Converts the data to an `Rc<dyn Any>` sharing its allocation.
+    fn into_any(self: Rc<Self>) -> Rc<dyn Any>;
+
+    /// Converts the data to an `Rc<dyn Trace>` sharing its allocation.
+    fn into_trace(self: Rc<Self>) -> Rc<dyn Trace>;
+}
+
+impl<T: Any + Trace> SegmentData for T {
+    fn into_any(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+
+    fn into_trace(self: Rc<Self>) -> Rc<dyn Trace> {
+        self
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+    impl<T: std::any::Any> Sealed for T {}
+}
+
+/// The data of a subtype defined by another `sem!` invocation or of
+/// a generic subtype, held by the data of its inherited base.
+#[doc(hidden)]
+pub struct Extension<V: ?Sized + 'static> {
+    /// Dispatches the methods of the inherited base to the subtype.
+    pub vtable: &'static V,
+    /// The data of the segment of the subtype.
+    pub data: Box<dyn Any>,
+}
+
+/// Marks a data type that other `sem!` invocations may extend, that is, one that
+/// is not sealed. It is implemented by the `sem!` and `sem_extend!` macros.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(message = "`{Self}` is sealed and cannot be extended by another `sem!` invocation")]
+pub trait Extensible {}
+
+/// Fails to compile unless the data type `T` may be extended.
+#[doc(hidden)]
+pub fn assert_extensible<T: Extensible + ?Sized>() {}
+
+/// An error of a conversion between data types or of an access to an entity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SemError {
+    /// The entity is not of the target data type of a conversion.
+    Contravariant {
+        /// The data type converted from.
+        from: &'static str,
+        /// The target data type.
+        to: &'static str,
+        /// The data type of the entity.
+        actual: &'static str,
+    },
+    /// The entity has been dropped, for instance along with its arena.
+    Dropped {
+        /// The data type through which the entity was accessed.
+        data_type: &'static str,
+    },
+    /// The entity is of the target generic data type of a conversion,
+    /// but with other generic arguments, so its data failed to downcast.
+    Downcast {
+        /// The target data type, with its generic arguments.
+        to: &'static str,
+    },
+}
+
+impl SemError {
+    /// Replaces the data type converted from, for a conversion
+    /// performed through intermediate data types.
+    #[doc(hidden)]
+    pub fn with_source(self, from: &'static str) -> Self {
+        match self {
+            Self::Contravariant { to, actual, .. } => Self::Contravariant { from, to, actual },
+            e => e,
+        }
+    }
+
+    /// Replaces the target data type, for a conversion
+    /// performed through intermediate data types.
+    #[doc(hidden)]
+    pub fn with_target(self, to: &'static str) -> Self {
+        match self {
+            Self::Contravariant { from, actual, .. } => Self::Contravariant { from, to, actual },
+            e => e,
+        }
+    }
+}
+
+impl std::fmt::Display for SemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Contravariant { to, actual, .. } => write!(f, "expected {to}, got {actual}"),
+            Self::Dropped { data_type } => write!(f, "{data_type} entity has been dropped"),
+            Self::Downcast { to } => write!(f, "expected {to}, got other generic arguments"),
+        }
+    }
+}
+
+impl std::error::Error for SemError {}
+
+/// Programs that `sem!` rejects, with one `compile_fail` doctest per diagnostic.
+#[cfg(doctest)]
+mod diagnostics {
+    /// A field cannot take an attribute that applies to type definitions only:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct Entity {
+    ///         #[derive(Clone)]
+    ///         let x: i32 = 0;
+    ///     }
+    /// }
+    /// ```
+    pub struct FieldTypeAttribute;
+
+    /// An override must have as many parameters as the overriden method:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct Entity {
+    ///         pub fn Entity() { super(); }
+    ///         pub fn m(&self, x: i32) -> i32 { x }
+    ///     }
+    ///     struct Foo: Entity {
+    ///         pub fn Foo() { super(); }
+    ///         pub override fn m(&self) -> i32 { 0 }
+    ///     }
+    /// }
+    /// ```
+    pub struct OverrideParameterCount;
+
+    /// An override must have the parameter types of the overriden method:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct Entity {
+    ///         pub fn Entity() { super(); }
+    ///         pub fn m(&self, x: i32) -> i32 { x }
+    ///     }
+    ///     struct Foo: Entity {
+    ///         pub fn Foo() { super(); }
+    ///         pub override fn m(&self, x: u8) -> i32 { x.into() }
+    ///     }
+    /// }
+    /// ```
+    pub struct OverrideParameterType;
+
+    /// An override must have the return type of the overriden method:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct Entity {
+    ///         pub fn Entity() { super(); }
+    ///         pub fn m(&self) -> i32 { 0 }
+    ///     }
+    ///     struct Foo: Entity {
+    ///         pub fn Foo() { super(); }
+    ///         pub override fn m(&self) -> u8 { 0 }
+    ///     }
+    /// }
+    /// ```
+    pub struct OverrideResultType;
+
+    /// An override must have as many generic parameters of each kind as the overriden method:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct Entity {
+    ///         pub fn Entity() { super(); }
+    ///         pub fn m<T: Default>(&self) -> T { T::default() }
+    ///     }
+    ///     struct Foo: Entity {
+    ///         pub fn Foo() { super(); }
+    ///         pub override fn m<'a, T: Default>(&self) -> T { T::default() }
+    ///     }
+    /// }
+    /// ```
+    pub struct OverrideGenerics;
+
+    /// A data type cannot inherit itself through other data types:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct A: C {
+    ///         pub fn A() { super(); }
+    ///     }
+    ///     struct B: A {
+    ///         pub fn B() { super(); }
+    ///     }
+    ///     struct C: B {
+    ///         pub fn C() { super(); }
+    ///     }
+    /// }
+    /// ```
+    pub struct InheritanceCycle;
+
+    /// A data type cannot inherit itself:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct D: D {
+    ///         pub fn D() { super(); }
+    ///     }
+    /// }
+    /// ```
+    pub struct SelfInheritance;
+
+    /// A concrete data type must override every abstract method it inherits:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     abstract struct Entity {
+    ///         pub abstract fn name(&self) -> String;
+    ///     }
+    ///     struct Foo: Entity {
+    ///         pub fn Foo() { super(); }
+    ///     }
+    /// }
+    /// ```
+    pub struct MissingAbstractOverride;
+
+    /// An abstract data type cannot be constructed:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     abstract struct Entity {
+    ///         pub fn Entity() { super(); }
+    ///     }
+    /// }
+    /// let arena = Arena::new();
+    /// let _ = Entity::new(&arena);
+    /// ```
+    pub struct AbstractConstruction;
+
+    /// An abstract method cannot be called through `super`:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     abstract struct Entity {
+    ///         pub abstract fn name(&self) -> String;
+    ///     }
+    ///     struct Foo: Entity {
+    ///         pub fn Foo() { super(); }
+    ///         pub override fn name(&self) -> String {
+    ///             super.name()
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub struct AbstractSuperCall;
+
+    /// A `sem!` invocation must define at least one data type:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {}
+    /// ```
+    pub struct NoDataType;
+
+    /// A constructor cannot have a receiver:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct Entity {
+    ///         pub fn Entity(&self) { super(); }
+    ///     }
+    /// }
+    /// ```
+    pub struct ConstructorReceiver;
+
+    /// A method can have a receiver only as its first parameter:
+    ///
+    /// ```compile_fail
+    /// use hydroperx_sem::sem;
+    /// sem! {
+    ///     type Arena = Arena;
+    ///     struct Entity {
+    ///         pub fn Entity() { super(); }
+    ///         pub fn m(&self, &self) {}
+    ///     }
+    /// }
+    /// ```
+    pub struct MethodReceiver;
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+        
+            /// My unified data type.
+            struct Entity {
+                pub fn Entity() {
+                    super();
+                }
+        
+                /// Empty, Foo, FooBar or FooQux
+                pub fn name(&self) -> String {
+                    "".into()
+                }
+
+                pub fn base_example(&self) -> String {
+                    "from base".into()
+                }
+
+                pub fn x(&self) -> f64 {
+                    0.0
+                }
+            }
+        
+            struct Foo: Entity {
+                pub fn Foo() {
+                    super();
+                }
+
+                #[inheritdoc]
+                pub override fn name(&self) -> String {
+                    "Foo".into()
+                }
+            }
+        
+            struct FooBar: Foo {
+                pub fn FooBar() {
+                    super();
+                }
+
+                #[inheritdoc]
+                pub override fn name(&self) -> String {
+                    "FooBar".into()
+                }
+
+                pub override fn base_example(&self) -> String {
+                    format!("from bar; {}", super.base_example())
+                }
+            }
+            
+            struct FooBarBar: FooBar {
+                let m_x: f64 = 0.0;
+                let ref m_y: String = "".into();
+
+                pub fn FooBarBar(x: f64, y: &str) {
+                    super();
+                    self.set_m_x(x);
+                    self.set_m_y(y.into());
+                }
+
+                #[inheritdoc]
+                pub override fn name(&self) -> String {
+                    "FooBarBar".into()
+                }
+
+                pub override fn x(&self) -> f64 {
+                    self.m_x()
+                }
+
+                pub override fn base_example(&self) -> String {
+                    format!("from {}; {}", self.m_y(), super.base_example())
+                }
+            }
+        
+            struct FooQux: Foo {
+                pub fn FooQux() {
+                    super();
+                }
+
+                #[inheritdoc]
+                pub override fn name(&self) -> String {
+                    "FooQux".into()
+                }
+            }
+        }
+
+        let arena = Arena::new();
+
+        let symbol = Foo::new(&arena);
+        let base_symbol: Entity = symbol.into();
+        assert_eq!("Foo", base_symbol.name());
+        assert_eq!(true, base_symbol.is::<Foo>());
+        assert_eq!(false, base_symbol.is::<FooBar>());
+        assert_eq!(false, base_symbol.is::<FooQux>());
+        assert_eq!("from base", base_symbol.base_example());
+        assert_eq!(0.0, base_symbol.x());
+
+        let symbol = FooBar::new(&arena);
+        let base_symbol: Entity = symbol.into();
+        assert_eq!("FooBar", base_symbol.name());
+        assert_eq!(true, base_symbol.is::<Foo>());
+        assert_eq!(true, base_symbol.is::<FooBar>());
+        assert_eq!(false, base_symbol.is::<FooBarBar>());
+        assert_eq!(false, base_symbol.is::<FooQux>());
+        assert_eq!("from bar; from base", base_symbol.base_example());
+        assert_eq!(0.0, base_symbol.x());
+
+        let symbol = FooBarBar::new(&arena, 10.0, "bar bar");
+        let base_symbol: Entity = symbol.into();
+        assert_eq!("FooBarBar", base_symbol.name());
+        assert_eq!(true, base_symbol.is::<Foo>());
+        assert_eq!(true, base_symbol.is::<FooBar>());
+        assert_eq!(true, base_symbol.is::<FooBarBar>());
+        assert_eq!(false, base_symbol.is::<FooQux>());
+        assert_eq!("from bar bar; from bar; from base", base_symbol.base_example());
+        assert_eq!(10.0, base_symbol.x());
+
+        let symbol = FooQux::new(&arena);
+        let base_symbol: Entity = symbol.into();
+        assert_eq!("FooQux", base_symbol.name());
+        assert_eq!(true, base_symbol.is::<Foo>());
+        assert_eq!(false, base_symbol.is::<FooBar>());
+        assert_eq!(true, base_symbol.is::<FooQux>());
+        assert_eq!(0.0, base_symbol.x());
+    }
+
+    #[test]
+    fn test_definition_order() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct FooBar: Foo {
+                pub fn FooBar() {
+                    super();
+                }
+
+                pub override fn name(&self) -> String {
+                    "FooBar".into()
+                }
+            }
+
+            struct Foo: Entity {
+                pub fn Foo() {
+                    super();
+                }
+            }
+
+            struct Entity {
+                pub fn Entity() {
+                    super();
+                }
+
+                pub fn name(&self) -> String {
+                    "".into()
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let symbol: Entity = FooBar::new(&arena).into();
+        assert_eq!("FooBar", symbol.name());
+        assert!(symbol.is::<Foo>());
+        assert!(symbol.is::<FooBar>());
+    }
+
+    #[test]
+    fn test_abstract() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            abstract struct Entity {
+                pub fn Entity() {
+                    super();
+                }
+
+                pub abstract fn name(&self) -> String;
+
+                pub fn describe(&self) -> String {
+                    format!("entity {}", self.name())
+                }
+            }
+
+            abstract struct Node: Entity {
+                pub abstract fn arity(&self) -> usize;
+            }
+
+            struct Leaf: Node {
+                pub fn Leaf() {
+                    super();
+                }
+
+                pub override fn name(&self) -> String {
+                    "Leaf".into()
+                }
+
+                pub override fn arity(&self) -> usize {
+                    0
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let node: Node = Leaf::new(&arena).into();
+        assert_eq!(0, node.arity());
+        assert_eq!("entity Leaf", node.describe());
+    }
+
+    #[test]
+    fn test_multiple_roots() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Type {
+                pub fn Type() {
+                    super();
+                }
+            }
+
+            struct FunctionType: Type {
+                pub fn FunctionType() {
+                    super();
+                }
+            }
+
+            struct Value {
+                let x: f64 = 0.0;
+
+                pub fn Value(x: f64) {
+                    super();
+                    self.set_x(x);
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let function_type: Type = FunctionType::new(&arena).into();
+        let value = Value::new(&arena, 10.0);
+        assert!(function_type.is::<FunctionType>());
+        assert_eq!(10.0, value.x());
+    }
+
+    #[test]
+    fn test_multiple_invocations() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Type {
+                pub fn Type() {
+                    super();
+                }
+            }
+        }
+
+        sem! {
+            mod sem = crate;
+
+            struct Scope {
+                pub fn Scope() {
+                    super();
+                }
+            }
+
+            struct PackageScope: Scope {
+                pub fn PackageScope() {
+                    super();
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let t = Type::new(&arena);
+        let scope: Scope = PackageScope::new(&arena).into();
+        assert!(scope.is::<PackageScope>());
+        assert_eq!(t, t.clone());
+    }
+
+    #[test]
+    fn test_extend() {
+        use crate::{sem, sem_extend};
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Entity {
+                pub fn Entity() {
+                    super();
+                }
+
+                pub fn name(&self) -> String {
+                    "entity".into()
+                }
+
+                pub fn kind(&self) -> i32 {
+                    0
+                }
+            }
+
+            struct Node: Entity {
+                pub fn Node() {
+                    super();
+                }
+
+                pub override fn name(&self) -> String {
+                    format!("node > {}", super.name())
+                }
+            }
+        }
+
+        sem_extend! {
+            mod sem = crate;
+
+            struct PluginNode: Node {
+                let z: i32 = 0;
+
+                pub fn PluginNode(z: i32) {
+                    super();
+                    self.set_z(z);
+                }
+
+                pub override fn name(&self) -> String {
+                    format!("plugin {} > {}", self.z(), super.name())
+                }
+            }
+
+            struct SubPluginNode: PluginNode {
+                pub fn SubPluginNode() {
+                    super(10);
+                }
+
+                pub override fn kind(&self) -> i32 {
+                    2
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let entity: Entity = Node::from(PluginNode::new(&arena, 5)).into();
+        assert_eq!(entity.name(), "plugin 5 > node > entity");
+        assert_eq!(entity.kind(), 0);
+        assert!(entity.is::<Node>());
+        assert!(entity.is::<PluginNode>());
+        assert!(!entity.is::<SubPluginNode>());
+        assert_eq!(entity.to::<PluginNode>().unwrap().z(), 5);
+
+        let entity: Entity = Node::from(PluginNode::from(SubPluginNode::new(&arena))).into();
+        assert_eq!(entity.kind(), 2);
+        assert!(entity.is::<SubPluginNode>());
+
+        let entity: Entity = Node::new(&arena).into();
+        assert_eq!(entity.name(), "node > entity");
+        assert!(!entity.is::<PluginNode>());
+    }
+
+    #[test]
+    fn test_generics() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Value {
+                pub fn Value() {
+                    super();
+                }
+
+                pub fn describe(&self) -> String {
+                    "value".into()
+                }
+
+                pub fn kind(&self) -> i32 {
+                    0
+                }
+            }
+
+            struct TypedValue<T: Clone + ::std::fmt::Debug + crate::Trace>: Value {
+                let ref v: Option<T> = None;
+
+                pub fn TypedValue(v: T) {
+                    super();
+                    self.set_v(Some(v));
+                }
+
+                pub fn get(&self) -> T {
+                    self.v().unwrap()
+                }
+
+                pub override fn describe(&self) -> String {
+                    format!("{:?} > {}", self.get(), super.describe())
+                }
+            }
+
+            struct IntValue: TypedValue<i32> {
+                pub fn IntValue(v: i32) {
+                    super(v);
+                }
+
+                pub override fn kind(&self) -> i32 {
+                    1
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let value: Value = TypedValue::new(&arena, "x".to_owned()).into();
+        assert_eq!(value.describe(), "\"x\" > value");
+        assert_eq!(value.kind(), 0);
+        assert!(value.is::<TypedValue<String>>());
+        assert!(!value.is::<TypedValue<i32>>());
+        assert_eq!(value.to::<TypedValue<String>>().unwrap().get(), "x");
+
+        let value: Value = IntValue::new(&arena, 10).into();
+        assert_eq!(value.describe(), "10 > value");
+        assert_eq!(value.kind(), 1);
+        assert!(value.is::<IntValue>());
+        let typed_value: TypedValue<i32> = value.to().unwrap();
+        assert_eq!(typed_value.get(), 10);
+        assert_eq!(typed_value.kind(), 1);
+    }
+
+    #[test]
+    fn test_field_visibility() {
+        mod fields {
+            use crate::sem;
+
+            sem! {
+                mod sem = crate;
+
+                type Arena = Arena;
+
+                pub struct Point {
+                    pub let x: f64 = 0.0;
+                    pub pub(crate) set let y: f64 = 0.0;
+                    pub set let ref name: String = "".into();
+                    let _z: f64 = 0.0;
+
+                    pub fn Point(name: &str) {
+                        super();
+                        self.set_name(name.into());
+                    }
+
+                    pub fn z(&self) -> f64 {
+                        self._z()
+                    }
+                }
+            }
+        }
+
+        use fields::Point;
+
+        let arena = crate::Arena::new();
+        let point = Point::new(&arena, "p");
+        point.set_x(1.0);
+        point.set_y(2.0);
+        assert_eq!(point.x(), 1.0);
+        assert_eq!(point.y(), 2.0);
+        assert_eq!(point.name(), "p");
+        assert_eq!(point.z(), 0.0);
+    }
+
+    #[test]
+    fn test_borrowing_accessors() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Node {
+                #[sem(untraced)]
+                let ref callback: Box<dyn Fn(i32) -> i32> = Box::new(|x| x);
+
+                pub fn Node() {
+                    super();
+                }
+
+                pub fn call(&self, x: i32) -> i32 {
+                    self.with_callback(|f| f(x))
+                }
+            }
+
+            struct ListNode: Node {
+                let ref items: Vec<String> = vec![];
+
+                pub fn ListNode() {
+                    super();
+                    self.set_callback(Box::new(|x| x * 2));
+                }
+
+                pub fn push(&self, item: &str) {
+                    self.with_items_mut(|items| items.push(item.into()));
+                }
+
+                pub fn first(&self) -> String {
+                    self.items_ref()[0].clone()
+                }
+
+                pub fn len(&self) -> usize {
+                    self.items_ref().len()
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let node = ListNode::new(&arena);
+        node.push("a");
+        node.push("b");
+        assert_eq!(node.first(), "a");
+        assert_eq!(node.len(), 2);
+        assert_eq!(node.call(5), 10);
+        assert_eq!(Node::new(&arena).call(5), 5);
+    }
+
+    #[test]
+    fn test_field_mutation() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Counter {
+                pub let count: i32 = 1;
+                pub let ref items: Vec<String> = vec!["default".into()];
+
+                pub fn Counter() {
+                    super();
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let counter = Counter::new(&arena);
+
+        assert_eq!(counter.replace_count(5), 1);
+        counter.update_count(|v| *v += 1);
+        assert_eq!(counter.count(), 6);
+        assert_eq!(counter.take_count(), 6);
+        assert_eq!(counter.count(), 0);
+        counter.reset_count();
+        assert_eq!(counter.count(), 1);
+
+        counter.update_items(|v| v.push("a".into()));
+        assert_eq!(counter.replace_items(vec![]), vec!["default", "a"]);
+        counter.update_items(|v| v.push("b".into()));
+        assert_eq!(counter.take_items(), vec!["b"]);
+        assert!(counter.items().is_empty());
+        counter.reset_items();
+        assert_eq!(counter.items(), vec!["default"]);
+    }
+
+    #[test]
+    fn test_field_attributes() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Entity {
+                /// The entity name.
+                #[inline]
+                #[must_use]
+                pub let ref name: String = "entity".into();
+                #[cfg(all())]
+                pub let enabled: bool = true;
+                #[cfg(any())]
+                pub let ref disabled: MissingType = MissingType::new();
+
+                pub fn Entity() {
+                    super();
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let entity = Entity::new(&arena);
+        assert_eq!(entity.name(), "entity");
+        assert!(entity.enabled());
+    }
+
+    #[test]
+    fn test_deep_dispatch() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct A {
+                pub fn A() {
+                    super();
+                }
+
+                pub fn name(&self) -> String {
+                    "a".into()
+                }
+            }
+
+            struct B: A {
+                pub fn B() {
+                    super();
+                }
+
+                pub override fn name(&self) -> String {
+                    format!("b > {}", super.name())
+                }
+            }
+
+            struct C: B {
+                pub fn C() {
+                    super();
+                }
+
+                pub fn level(&self) -> i32 {
+                    3
+                }
+            }
+
+            struct D: C {
+                pub fn D() {
+                    super();
+                }
+            }
+
+            struct E: D {
+                pub fn E() {
+                    super();
+                }
+
+                pub override fn name(&self) -> String {
+                    format!("e > {}", super.name())
+                }
+
+                pub override fn level(&self) -> i32 {
+                    5
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let a: A = E::new(&arena).into();
+        assert_eq!(a.name(), "e > b > a");
+        assert_eq!(a.to::<C>().unwrap().level(), 5);
+        let a: A = D::new(&arena).into();
+        assert_eq!(a.name(), "b > a");
+        assert_eq!(a.to::<C>().unwrap().level(), 3);
+        assert_eq!(A::new(&arena).name(), "a");
+    }
+
+    #[test]
+    fn test_deep_fields() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Node {
+                let depth: usize = 0;
+                let ref name: String = "node".into();
+            }
+
+            struct Expr: Node {
+                let ref ty: String = "any".into();
+            }
+
+            struct Binary: Expr {
+                let operator: char = '+';
+            }
+
+            struct Add: Binary {
+                let ref operands: Vec<i32> = vec![];
+
+                pub fn Add(operands: Vec<i32>) {
+                    super();
+                    self.set_operands(operands);
+                    self.set_name("add".into());
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let add = Add::new(&arena, vec![1, 2]);
+        let node: Node = add.clone().into();
+        node.set_depth(4);
+        let expr: Expr = node.to::<Expr>().unwrap();
+        expr.set_ty("i32".into());
+        assert_eq!(add.depth(), 4);
+        assert_eq!(add.name(), "add");
+        assert_eq!(add.ty(), "i32");
+        assert_eq!(add.operator(), '+');
+        assert_eq!(add.operands(), vec![1, 2]);
+        assert_eq!(node.to::<Binary>().unwrap().operator(), '+');
+        assert!(Expr::new(&arena).to::<Binary>().is_err());
+    }
+
+    #[test]
+    fn test_index_handles() {
+        use crate::sem;
+        use std::collections::HashMap;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+            type Handle = Index;
+
+            pub struct Entity {
+                let ref name: String = "".into();
+
+                pub fn Entity(name: String) {
+                    super();
+                    self.set_name(name);
+                }
+
+                pub fn kind(&self) -> String {
+                    "entity".into()
+                }
+            }
+
+            pub struct Item<T: crate::Trace>: Entity {
+                let ref value: Option<T> = None;
+
+                pub fn Item(name: String, value: T) {
+                    super(name);
+                    self.set_value(Some(value));
+                }
+
+                pub override fn kind(&self) -> String {
+                    "item".into()
+                }
+            }
+        }
+
+        fn is_copy<T: Copy>(_: T) {}
+
+        let arena = Arena::new();
+        let entity = Entity::new(&arena, "a".into());
+        let item = Item::<i32>::new(&arena, "b".into(), 10);
+        is_copy(entity);
+        is_copy(item);
+
+        let mut side_table = HashMap::new();
+        side_table.insert(entity, 1);
+        side_table.insert(item.into(), 2);
+        assert_eq!(side_table[&entity], 1);
+        assert_eq!(side_table[&Entity::from(item)], 2);
+
+        let base: Entity = item.into();
+        assert_eq!(base.kind(), "item");
+        assert_eq!(base.to::<Item<i32>>().unwrap().value(), Some(10));
+        assert!(base.is::<Item<i32>>());
+        assert!(!entity.is::<Item<i32>>());
+
+        // A handle to a freed object is detected, even
+        // if its slot is reused by another arena.
+        drop(arena);
+        let arena = Arena::new();
+        let other = Entity::new(&arena, "c".into());
+        assert_ne!(other, entity);
+        assert_eq!(other.name(), "c");
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| entity.name())).is_err());
+
+        // Freeing a handle whose slot has been reused does not free the new entity.
+        assert!(arena.free(&other));
+        let reused = Entity::new(&arena, "d".into());
+        assert!(!arena.free(&other));
+        assert!(reused.is_alive());
+        assert_eq!(arena.count_of::<Entity>(), 1);
+    }
+
+    #[test]
+    fn test_sync_handles() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+            type Handle = Sync;
+
+            pub struct Symbol {
+                pub let ref name: String = "".into();
+                pub let uses: u32 = 0;
+
+                pub fn Symbol(name: String) {
+                    super();
+                    self.set_name(name);
+                }
+
+                pub fn describe(&self) -> String {
+                    self.name()
+                }
+            }
+
+            pub struct Function: Symbol {
+                pub fn Function(name: String) {
+                    super(name);
+                }
+
+                pub override fn describe(&self) -> String {
+                    format!("fn {}", self.name())
+                }
+            }
+
+            pub struct Constant<T: crate::sync::Trace>: Symbol {
+                pub let ref value: Option<T> = None;
+
+                pub fn Constant(name: String, value: T) {
+                    super(name);
+                    self.set_value(Some(value));
+                }
+
+                pub override fn describe(&self) -> String {
+                    format!("const {}", self.name())
+                }
+            }
+        }
+
+        fn is_send_sync<T: Send + Sync>(_: &T) {}
+
+        let arena = Arena::new();
+        let symbols: Vec<Symbol> = vec![
+            Function::new(&arena, "f".into()).into(),
+            Constant::<i32>::new(&arena, "c".into(), 3).into(),
+        ];
+        is_send_sync(&arena);
+        is_send_sync(&symbols[0]);
+
+        let descriptions = std::thread::scope(|scope| {
+            let threads = symbols.iter().map(|symbol| scope.spawn(move || {
+                symbol.update_uses(|uses| *uses += 1);
+                symbol.describe()
+            })).collect::<Vec<_>>();
+            threads.into_iter().map(|thread| thread.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(descriptions, ["fn f", "const c"]);
+        assert_eq!(symbols[0].uses(), 1);
+
+        // Concurrent updates of the same field are not lost.
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        symbols[1].update_uses(|uses| *uses += 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(symbols[1].uses(), 4001);
+        assert_eq!(*symbols[0].name_ref(), "f");
+        assert_eq!(symbols[1].to::<Constant<i32>>().unwrap().value(), Some(3));
+        assert!(symbols[1].to::<Function>().is_err());
+    }
+
+    #[test]
+    fn test_dropped_entities() {
+        use crate::{sem, SemError};
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            pub struct Entity {
+                pub let id: u32 = 0;
+                pub let ref name: String = "".into();
+            }
+
+            pub struct Item<T: crate::Trace>: Entity {
+                pub let ref value: Option<T> = None;
+            }
+        }
+
+        let arena = Arena::new();
+        let entity = Entity::new(&arena);
+        let item = Item::<i32>::new(&arena);
+        assert!(entity.is_alive() && item.is_alive());
+        entity.try_set_id(1).unwrap();
+        item.try_set_value(Some(2)).unwrap();
+        assert_eq!(entity.try_id().unwrap(), 1);
+        assert_eq!(item.try_value().unwrap(), Some(2));
+        assert_eq!(*item.try_name_ref().unwrap(), "");
+
+        let base: Entity = item.clone().into();
+        drop(arena);
+        assert!(!entity.is_alive() && !item.is_alive());
+        assert!(matches!(entity.try_id(), Err(SemError::Dropped { .. })));
+        assert!(matches!(entity.try_set_name("a".into()), Err(SemError::Dropped { .. })));
+        assert!(matches!(item.try_value(), Err(SemError::Dropped { .. })));
+        assert!(matches!(item.try_name_ref(), Err(SemError::Dropped { .. })));
+        assert!(matches!(base.to::<Item<i32>>(), Err(SemError::Dropped { .. })));
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        use crate::{sem, SemError};
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            pub struct Symbol {}
+
+            pub struct Type: Symbol {}
+
+            pub struct FunctionType: Type {}
+
+            pub struct ClassType: Type {}
+
+            pub struct Constant<T: crate::Trace>: Symbol {
+                pub let ref value: Option<T> = None;
+            }
+        }
+
+        let arena = Arena::new();
+        let class: Symbol = ClassType::new(&arena).into();
+        let function: Symbol = FunctionType::new(&arena).into();
+        let constant: Symbol = Constant::<i32>::new(&arena).into();
+
+        let e = class.to::<FunctionType>().unwrap_err();
+        assert_eq!(e, SemError::Contravariant { from: "Symbol", to: "FunctionType", actual: "ClassType" });
+        assert_eq!(e.to_string(), "expected FunctionType, got ClassType");
+        let e = ClassType::try_from(class.clone()).unwrap().to::<FunctionType>().unwrap_err();
+        assert_eq!(e, SemError::Contravariant { from: "ClassType", to: "FunctionType", actual: "ClassType" });
+        let e = function.to::<Constant<i32>>().unwrap_err();
+        assert_eq!(e, SemError::Contravariant { from: "Symbol", to: "Constant", actual: "FunctionType" });
+        let e = constant.to::<Type>().unwrap_err();
+        assert_eq!(e, SemError::Contravariant { from: "Symbol", to: "Type", actual: "Constant" });
+        assert!(matches!(constant.to::<Constant<u8>>(), Err(SemError::Downcast { .. })));
+
+        drop(arena);
+        let e: Box<dyn std::error::Error> = Box::new(class.to::<Type>().unwrap_err());
+        assert_eq!(e.to_string(), "Symbol entity has been dropped");
+    }
+
+    #[test]
+    fn test_collect() {
+        use crate::util::SharedArray;
+
+        // Fields referring to the data types need
+        // the data types to be module items.
+        mod model {
+            use crate::sem;
+            use crate::util::{SharedArray, SharedMap};
+
+            sem! {
+                mod sem = crate;
+
+                type Arena = Arena;
+
+                pub struct Node {
+                    pub let ref next: Option<Node> = None;
+                    pub let ref children: SharedArray<Node> = SharedArray::new();
+                    pub let ref names: SharedMap<&'static str, Node> = SharedMap::new();
+                    pub let ref pairs: Vec<(i32, Node)> = vec![];
+                    #[sem(untraced)]
+                    pub let ref cache: Option<Node> = None;
+
+                    pub fn Node() {
+                        super();
+                    }
+                }
+
+                pub struct Leaf<T: crate::Trace>: Node {
+                    pub let ref value: Option<T> = None;
+                    pub let ref other: Option<Node> = None;
+
+                    pub fn Leaf() {
+                        super();
+                    }
+                }
+            }
+        }
+        use model::*;
+
+        let arena = Arena::new();
+        let root = Node::new(&arena);
+        let a = Node::new(&arena);
+        let b = Node::new(&arena);
+        let c = Node::new(&arena);
+        let d = Node::new(&arena);
+        let e = Node::new(&arena);
+        let leaf = Leaf::<i32>::new(&arena);
+
+        // root <-> a, b <-> c, root -> [d, leaf], leaf -> e
+        root.set_next(Some(a.clone()));
+        a.set_next(Some(root.clone()));
+        b.set_next(Some(c.clone()));
+        c.set_next(Some(b.clone()));
+        root.children().push(d.clone());
+        root.children().push(leaf.clone().into());
+        leaf.set_other(Some(e.clone()));
+
+        arena.collect(&[&root]);
+        assert!(root.is_alive() && a.is_alive() && d.is_alive() && leaf.is_alive() && e.is_alive());
+        assert!(!b.is_alive() && !c.is_alive());
+
+        // Entities in maps, tuples and generic fields are followed,
+        // unlike entities in untraced fields.
+        let f = Node::new(&arena);
+        let g = Node::new(&arena);
+        let h = Node::new(&arena);
+        let i = Node::new(&arena);
+        let node_leaf = Leaf::<Node>::new(&arena);
+        node_leaf.set_value(Some(h.clone()));
+        a.names().set("f", f.clone());
+        a.set_pairs(vec![(1, g.clone()), (2, node_leaf.clone().into())]);
+        a.set_cache(Some(i.clone()));
+        arena.collect(&[&root]);
+        assert!(f.is_alive() && g.is_alive() && node_leaf.is_alive() && h.is_alive());
+        assert!(!i.is_alive());
+        a.set_pairs(vec![]);
+        a.names().clear();
+
+        root.set_children(SharedArray::new());
+        arena.collect(&[&root]);
+        assert!(root.is_alive() && a.is_alive());
+        assert!(!d.is_alive() && !leaf.is_alive() && !e.is_alive());
+
+        arena.collect(&[]);
+        assert!(!root.is_alive() && !a.is_alive());
+    }
+
+    #[test]
+    fn test_iteration() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Entity {
+                pub let id: u32 = 0;
+
+                pub fn Entity(id: u32) {
+                    super();
+                    self.set_id(id);
+                }
+            }
+
+            struct Symbol: Entity {
+                pub fn Symbol(id: u32) {
+                    super(id);
+                }
+            }
+
+            struct FunctionSymbol: Symbol {
+                pub fn FunctionSymbol(id: u32) {
+                    super(id);
+                }
+            }
+
+            struct TypedSymbol<T>: Symbol {
+                pub fn TypedSymbol(id: u32) {
+                    super(id);
+                }
+            }
+
+            struct Scope {}
+        }
+
+        let arena = Arena::new();
+        assert!(arena.is_empty());
+        Entity::new(&arena, 1);
+        Symbol::new(&arena, 2);
+        FunctionSymbol::new(&arena, 3);
+        Scope::new(&arena);
+        FunctionSymbol::new(&arena, 4);
+        TypedSymbol::<i32>::new(&arena, 5);
+        TypedSymbol::<String>::new(&arena, 6);
+
+        assert_eq!(arena.len(), 7);
+        assert_eq!(arena.iter_of::<Entity>().map(|e| e.id()).collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(arena.iter_of::<Symbol>().map(|e| e.id()).collect::<Vec<_>>(), [2, 3, 4, 5, 6]);
+        assert_eq!(arena.iter_of::<FunctionSymbol>().map(|e| e.id()).collect::<Vec<_>>(), [3, 4]);
+        assert_eq!(arena.iter_of::<TypedSymbol<i32>>().map(|e| e.id()).collect::<Vec<_>>(), [5]);
+        assert_eq!(arena.count_of::<Scope>(), 1);
+        assert_eq!(arena.count_of::<TypedSymbol<String>>(), 1);
+        assert_eq!(arena.count_of::<Symbol>(), 5);
+
+        // Entities allocated during the iteration are not seen.
+        for e in arena.iter_of::<FunctionSymbol>() {
+            FunctionSymbol::new(&arena, e.id() + 10);
+        }
+        assert_eq!(arena.count_of::<FunctionSymbol>(), 4);
+
+        // Entities freed during the iteration are not seen, nor counted.
+        let mut ids = vec![];
+        for e in arena.iter_of::<Symbol>() {
+            ids.push(e.id());
+            if e.id() == 2 {
+                let f = arena.iter_of::<FunctionSymbol>().next().unwrap();
+                assert!(arena.free(&f));
+            }
+        }
+        assert_eq!(ids, [2, 4, 5, 6, 13, 14]);
+        assert_eq!(arena.count_of::<FunctionSymbol>(), 3);
+        assert_eq!(arena.count_of::<Entity>(), 7);
+        assert_eq!(arena.len(), 8);
+    }
+
+    #[test]
+    fn test_free() {
+        use crate::{sem, SemError};
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            struct Entity {
+                pub let id: u32 = 0;
+
+                pub fn Entity(id: u32) {
+                    super();
+                    self.set_id(id);
+                }
+            }
+
+            struct Declaration: Entity {
+                pub fn Declaration(id: u32) {
+                    super(id);
+                }
+            }
+
+            struct TypedDeclaration<T: crate::Trace>: Declaration {
+                pub let ref value: Option<T> = None;
+
+                pub fn TypedDeclaration(id: u32) {
+                    super(id);
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let a = Entity::new(&arena, 1);
+        let b = Declaration::new(&arena, 2);
+        let c = TypedDeclaration::<i32>::new(&arena, 3);
+        assert_eq!(arena.len(), 3);
+
+        assert!(arena.free(&b));
+        assert!(!b.is_alive() && a.is_alive() && c.is_alive());
+        assert_eq!(b.try_id(), Err(SemError::Dropped { data_type: "Entity" }));
+        let message = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| b.id())).unwrap_err();
+        assert_eq!(message.downcast_ref::<String>().unwrap(), "Entity entity has been dropped");
+        assert!(!arena.free(&b));
+
+        let c_base: Entity = c.clone().into();
+        assert!(arena.free(&c_base));
+        assert!(!c.is_alive());
+        assert_eq!(c.try_value(), Err(SemError::Dropped { data_type: "TypedDeclaration" }));
+        assert_eq!(arena.iter_of::<Entity>().map(|e| e.id()).collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn test_regions() {
+        // Fields referring to the data types need
+        // the data types to be module items.
+        mod model {
+            use crate::sem;
+
+            sem! {
+                mod sem = crate;
+
+                type Arena = Arena;
+
+                pub struct Type {
+                    pub let ref bound: Option<Type> = None;
+
+                    pub fn Type() {
+                        super();
+                    }
+                }
+            }
+        }
+        use model::*;
+
+        let arena = Arena::new();
+        let t = Type::new(&arena);
+        let region = arena.region();
+        assert!(std::ptr::eq(region.parent(), &arena));
+
+        let v = Type::new(&region);
+        v.set_bound(Some(t.clone()));
+        assert_eq!(v.bound(), Some(t.clone()));
+        assert_eq!((arena.len(), region.len()), (1, 1));
+
+        let nested = region.region();
+        let w = Type::new(&nested);
+        w.set_bound(Some(v.clone()));
+        drop(nested);
+        assert!(!w.is_alive() && v.is_alive());
+
+        region.reset();
+        assert!(!v.is_alive() && t.is_alive());
+        assert!(region.is_empty());
+
+        let v = Type::new(&region);
+        drop(region);
+        assert!(!v.is_alive() && t.is_alive());
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    fn test_override_parameter_names() {
+        use crate::sem;
+
+        type Label = String;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            pub struct Entity {
+                let k: i32 = 0;
+
+                pub fn Entity((a, b): (i32, i32)) {
+                    super();
+                    self.set_k(a + b);
+                }
+
+                pub fn m(&self, mut x: i32, _: i32) -> i32 {
+                    x += self.k();
+                    x
+                }
+
+                pub fn first<'a, T: Clone>(&self, items: &'a [T]) -> Option<&'a T> {
+                    items.first()
+                }
+
+                pub fn label(&self, prefix: String) -> Option<String> {
+                    Some(prefix)
+                }
+            }
+
+            pub struct Foo: Entity {
+                pub fn Foo(mut k: i32) {
+                    super((k, 0));
+                    k += 1;
+                    self.set_k(k);
+                }
+
+                pub override fn m(&self, a: i32, b: i32) -> i32 {
+                    a * b + super.m(a, b)
+                }
+
+                // Generic parameters are matched by position.
+                pub override fn first<'b, U: Clone>(&self, items: &'b [U]) -> Option<&'b U> {
+                    items.last()
+                }
+
+                // Types may be spelled differently, such as through an alias.
+                pub override fn label(&self, prefix: std::string::String) -> core::option::Option<Label> {
+                    Some(format!("{prefix}foo"))
+                }
+            }
+        }
+
+        let arena = Arena::new();
+        let entity = Entity::new(&arena, (1, 2));
+        assert_eq!(entity.m(3, 4), 6);
+        let entity: Entity = Foo::new(&arena, 2).into();
+        assert_eq!(entity.m(3, 4), 18);
+        assert_eq!(entity.first(&[1, 2]), Some(&2));
+        assert_eq!(entity.label("a ".into()), Some("a foo".into()));
+    }
+
+    #[test]
+    fn test_sealed() {
+        use crate::sem;
+
+        sem! {
+            mod sem = crate;
+
+            type Arena = Arena;
+
+            pub abstract sealed struct Entity {
+                pub abstract fn name(&self) -> String;
+            }
+
+            pub sealed struct Foo: Entity {
+                let x: i32 = 0;
+
+                pub fn Foo(x: i32) {
+                    super();
+                    self.set_x(x);
+                }
+
+                pub override fn name(&self) -> String {
+                    format!("foo {}", self.x())
+                }
+            }
+
+            pub sealed struct Bar: Foo {
+                pub fn Bar() {
+                    super(1);
+                }
+
+                pub override fn name(&self) -> String {
+                    format!("bar > {}", super.name())
+                }
+            }
+
+            pub abstract sealed struct Named: Entity {}
+        }
+
+        let arena = Arena::new();
+        let entity: Entity = Foo::new(&arena, 0).into();
+        assert!(!entity.is::<Named>());
+        assert!(Named::try_from(entity.clone()).is_err());
+        assert_eq!(entity.name(), "foo 0");
+        let entity: Entity = Bar::new(&arena).into();
+        assert_eq!(entity.name(), "bar > foo 1");
+        assert!(entity.is::<Foo>());
+        assert_eq!(Foo::try_from(entity).unwrap().x(), 1);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,310 @@
+//! Thread-safe counterparts of the arena and of the field storage,
+//! used by data types defined under the `type Handle = Sync;` directive.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::ops::Deref;
+use crate::{DataType, FromObject, Objects, Region};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
+
+/// An arena holding the data of all root hierarchies of a `sem!`
+/// invocation, which may be shared across threads.
+pub struct Arena {
+    data: Mutex<Objects<Arc<dyn Any + Send + Sync>>>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self {
+            data: Mutex::new(Objects::new()),
+        }
+    }
+
+    /// Allocates the data of an entity of the data type `D`.
+    pub fn allocate<D: DataType, T: Send + Sync + 'static>(&self, value: T) -> Weak<T> {
+        let obj = Arc::new(value);
+        lock(&self.data).insert::<D>(Arc::as_ptr(&obj) as *const () as usize, obj.clone());
+        Arc::downgrade(&obj)
+    }
+
+    /// The number of objects in the arena.
+    pub fn len(&self) -> usize {
+        lock(&self.data).len()
+    }
+
+    /// Whether the arena holds no objects.
+    pub fn is_empty(&self) -> bool {
+        lock(&self.data).len() == 0
+    }
+
+    /// Iterates the entities of the arena that are of the data type `T`, in
+    /// allocation order. With the basemost data type of a hierarchy, all
+    /// entities of the hierarchy are iterated.
+    ///
+    /// The iteration sees the entities allocated before it starts
+    /// and not freed before it reaches them.
+    pub fn iter_of<T: DataType>(&self) -> impl Iterator<Item = T> + '_ where T::Root: FromObject<Arc<dyn Any + Send + Sync>> {
+        let mut cursor = lock(&self.data).cursor::<T>();
+        std::iter::from_fn(move || loop {
+            let obj = lock(&self.data).next(&mut cursor)?;
+            if let Some(entity) = T::Root::from_object(&obj).and_then(|root| T::from_root(root).ok()) {
+                return Some(entity);
+            }
+        })
+    }
+
+    /// The number of entities of the arena that are of the data type `T`.
+    pub fn count_of<T: DataType>(&self) -> usize {
+        lock(&self.data).count::<T>()
+    }
+
+    /// Creates a region: a child arena whose entities may refer to the entities
+    /// of this arena and are freed at once when the region is dropped or reset.
+    pub fn region(&self) -> Region<'_, Self> {
+        Region::new(Self::new(), self)
+    }
+
+    /// Frees all entities of the arena at once.
+    pub fn reset(&self) {
+        // The objects are dropped after the arena is released,
+        // since dropping an object may access the arena.
+        let data = lock(&self.data).take();
+        drop(data);
+    }
+
+    /// Frees an entity right away, returning whether it was held by the arena.
+    /// Later accesses to the entity fail with `SemError::Dropped`.
+    ///
+    /// Freeing an entity takes time logarithmic in the number of objects of the arena.
+    pub fn free<T: DataType>(&self, entity: &T) -> bool where T::Root: FromObject<Arc<dyn Any + Send + Sync>> {
+        let root = entity.clone().into_root();
+        let mut data = lock(&self.data);
+        let Some(obj) = data.remove(root.object_key()) else {
+            return false;
+        };
+        // The object is dropped after the arena is released,
+        // since dropping an object may access the arena.
+        drop(data);
+        drop(obj);
+        true
+    }
+
+    /// Frees dead objects from the arena, that is, objects to which no handle
+    /// remains. Note that a call to `clean()` may be expensive; therefore it is
+    /// recommended to call it after a long processing has been done with the arena.
+    pub fn clean(&self) {
+        self.sweep(|obj| Arc::weak_count(obj) != 0 || Arc::strong_count(obj) != 1);
+    }
+
+    /// Frees the objects unreachable from `roots`, following the entities held by
+    /// the fields of reachable entities, including in reference cycles. The objects
+    /// are marked from the roots, then swept in a single pass over the arena.
+    pub fn collect(&self, roots: &[&dyn Trace]) {
+        let mut tracer = Tracer::new();
+        for root in roots {
+            root.trace(&mut tracer);
+        }
+        while let Some(data) = tracer.pending.pop() {
+            data.trace(&mut tracer);
+        }
+        self.sweep(|obj| tracer.marked.contains(&Arc::as_ptr(obj).cast::<()>()));
+    }
+
+    /// Frees the objects not retained by `f`.
+    fn sweep(&self, f: impl FnMut(&Arc<dyn Any + Send + Sync>) -> bool) {
+        // The objects are dropped after the arena is released,
+        // since dropping an object may access the arena.
+        let dead = lock(&self.data).retain(f);
+        drop(dead);
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Follows the entities held by a value, for `Arena::collect`.
+///
+/// It is implemented by the data types of the `sem!` and `sem_extend!`
+/// macros under `type Handle = Sync;` and by common containers
+/// of values implementing it.
+///
+/// The type of each field of a data type must implement it, unless the field
+/// is marked with `#[sem(untraced)]`, in which case `collect()` does not follow
+/// the entities it holds.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not implement `Trace`",
+    note = "mark the field with `#[sem(untraced)]` if it holds no entities that `collect()` must follow",
+)]
+pub trait Trace {
+    /// Marks the entities held by the value as reachable.
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+/// Marks the entities reachable from the roots of `Arena::collect`.
+pub struct Tracer {
+    marked: HashSet<*const ()>,
+    pending: Vec<Arc<dyn Trace>>,
+}
+
+impl Tracer {
+    fn new() -> Self {
+        Self {
+            marked: HashSet::new(),
+            pending: vec![],
+        }
+    }
+
+    /// Marks the data of an entity as reachable, tracing
+    /// its fields unless it has already been marked.
+    #[doc(hidden)]
+    pub fn mark<D: SegmentData + ?Sized>(&mut self, data: Arc<D>) {
+        if self.marked.insert(Arc::as_ptr(&data).cast::<()>()) {
+            self.pending.push(data.into_trace());
+        }
+    }
+}
+
+impl<T: Copy + Trace> Trace for Cell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.get().trace(tracer);
+    }
+}
+
+impl<T: Trace> Trace for RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.borrow().trace(tracer);
+    }
+}
+
+impl_std_trace!();
+
+/// A mutable memory location for a `Copy` field, guarded by a lock.
+/// It mirrors the API of `std::cell::Cell` used by the generated accessors.
+pub struct Cell<T>(Mutex<T>);
+
+impl<T> Cell<T> {
+    pub fn new(value: T) -> Self {
+        Self(Mutex::new(value))
+    }
+
+    pub fn set(&self, value: T) {
+        *lock(&self.0) = value;
+    }
+
+    pub fn replace(&self, value: T) -> T {
+        std::mem::replace(&mut *lock(&self.0), value)
+    }
+
+    pub fn take(&self) -> T where T: Default {
+        self.replace(T::default())
+    }
+
+    /// Modifies the value while holding the lock, so that no other
+    /// thread writes the value in between. Accessing the same field
+    /// from `f` deadlocks.
+    pub fn update(&self, f: impl FnOnce(&mut T)) {
+        f(&mut lock(&self.0));
+    }
+}
+
+impl<T: Copy> Cell<T> {
+    pub fn get(&self) -> T {
+        *lock(&self.0)
+    }
+}
+
+/// A mutable memory location for a `ref` field, guarded by a read-write lock.
+/// It mirrors the API of `std::cell::RefCell` used by the generated accessors.
+///
+/// Unlike `std::cell::RefCell`, borrowing the field mutably while it is
+/// borrowed by the same thread blocks rather than panics.
+pub struct RefCell<T>(RwLock<T>);
+
+impl<T> RefCell<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(value))
+    }
+
+    pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, T> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn replace(&self, value: T) -> T {
+        std::mem::replace(&mut *self.borrow_mut(), value)
+    }
+
+    pub fn take(&self) -> T where T: Default {
+        self.replace(T::default())
+    }
+}
+
+/// A borrow of a `ref` field, returned by the `fieldname_ref()` accessor.
+/// It keeps the data of the field alive while borrowed.
+pub struct FieldRef<'a, T: 'static> {
+    // Dropped before the data.
+    value: RwLockReadGuard<'a, T>,
+    _data: Arc<dyn Any + Send + Sync>,
+}
+
+impl<'a, T: 'static> FieldRef<'a, T> {
+    /// Borrows the field projected by `field` from `data`.
+    #[doc(hidden)]
+    pub fn new<D: SegmentData + ?Sized>(data: Arc<D>, field: impl for<'b> FnOnce(&'b D) -> &'b RefCell<T>) -> Self {
+        let cell: *const RefCell<T> = field(&data);
+        // SAFETY: the cell lies within the allocation of `data`, which does not
+        // move and outlives the borrow, as `value` is dropped first.
+        let value = unsafe { &*cell }.borrow();
+        Self { value, _data: data.into_any() }
+    }
+}
+
+impl<'a, T: 'static> Deref for FieldRef<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// The data of the data types of a segment, allocated at once. It is
+/// a supertrait of the data traits generated by the `sem!` macro.
+#[doc(hidden)]
+pub trait SegmentData: Any + Send + Sync + Trace + crate::sealed::Sealed {
+    /// Converts the data to an `Arc<dyn Any + Send + Sync>` sharing its allocation.
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+
+    /// Converts the data to an `Arc<dyn Trace>` sharing its allocation.
+    fn into_trace(self: Arc<Self>) -> Arc<dyn Trace>;
+}
+
+impl<T: Any + Send + Sync + Trace> SegmentData for T {
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn into_trace(self: Arc<Self>) -> Arc<dyn Trace> {
+        self
+    }
+}
+
+/// The data of a subtype defined by another `sem!` invocation or of
+/// a generic subtype, held by the data of its inherited base.
+#[doc(hidden)]
+pub struct Extension<V: ?Sized + 'static> {
+    /// Dispatches the methods of the inherited base to the subtype.
+    pub vtable: &'static V,
+    /// The data of the segment of the subtype.
+    pub data: Box<dyn Any + Send + Sync>,
+}
+
+/// Locks a mutex, ignoring poisoning, since the guarded
+/// values are left consistent by the generated accessors.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
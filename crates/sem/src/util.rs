@@ -1,11 +1,11 @@
-//! Utilities for semantic modeling.
-
-#[macro_use]
-mod shared_array;
-pub use shared_array::*;
-pub use crate::shared_array;
-
-#[macro_use]
-mod shared_map;
-pub use shared_map::*;
+//! Utilities for semantic modeling.
+
+#[macro_use]
+mod shared_array;
+pub use shared_array::*;
+pub use crate::shared_array;
+
+#[macro_use]
+mod shared_map;
+pub use shared_map::*;
 pub use crate::shared_map;
\ No newline at end of file